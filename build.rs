@@ -0,0 +1,5 @@
+// sqlx::migrate! embeds the migrations at compile time,
+// so a new or changed migration has to trigger a rebuild
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
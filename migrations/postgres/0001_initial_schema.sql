@@ -0,0 +1,33 @@
+-- The schema main() used to build on every startup before there were
+-- migrations. Databases that already have these tables adopt them as is.
+--
+-- Everything before 0010 was also built by main() at some point, so those
+-- migrations only add what isn't there yet and can run over a database
+-- that already has any part of them.
+
+CREATE TABLE IF NOT EXISTS players (
+    id SERIAL PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS games (
+    id SERIAL PRIMARY KEY,
+    start_datetime TIMESTAMP WITH TIME ZONE NOT NULL,
+    end_datetime TIMESTAMP WITH TIME ZONE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS games_players (
+    id SERIAL PRIMARY KEY,
+    game_id INTEGER NOT NULL,
+    player_id INTEGER NOT NULL,
+    rank INTEGER NOT NULL,
+    FOREIGN KEY (game_id) REFERENCES games(id),
+    FOREIGN KEY (player_id) REFERENCES players(id)
+);
+
+CREATE TABLE IF NOT EXISTS commanders (
+    id SERIAL PRIMARY KEY,
+    games_players_id INTEGER NOT NULL,
+    commander TEXT NOT NULL,
+    FOREIGN KEY (games_players_id) REFERENCES games_players(id)
+);
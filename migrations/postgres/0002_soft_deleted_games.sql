@@ -0,0 +1,2 @@
+-- Deleted games keep their rows so they can be restored
+ALTER TABLE games ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP WITH TIME ZONE;
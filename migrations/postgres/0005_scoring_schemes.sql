@@ -0,0 +1,6 @@
+-- definition is JSON, see scoring::SchemeDefinition
+CREATE TABLE IF NOT EXISTS scoring_schemes (
+    id SERIAL PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    definition TEXT NOT NULL
+);
@@ -0,0 +1,32 @@
+CREATE TABLE IF NOT EXISTS playgroups (
+    id SERIAL PRIMARY KEY,
+    slug TEXT UNIQUE NOT NULL,
+    name TEXT NOT NULL
+);
+
+-- Anything from before playgroups existed goes into the default group
+INSERT INTO playgroups (slug, name) VALUES ('default', 'Default') ON CONFLICT (slug) DO NOTHING;
+
+-- Players, games, seasons and scoring schemes all belong to a playgroup
+-- and names only have to be unique within a group
+ALTER TABLE players ADD COLUMN IF NOT EXISTS playgroup_id INTEGER REFERENCES playgroups(id);
+UPDATE players SET playgroup_id = (SELECT id FROM playgroups WHERE slug = 'default') WHERE playgroup_id IS NULL;
+ALTER TABLE players ALTER COLUMN playgroup_id SET NOT NULL;
+ALTER TABLE players DROP CONSTRAINT IF EXISTS players_name_key;
+CREATE UNIQUE INDEX IF NOT EXISTS players_playgroup_id_name_key ON players (playgroup_id, name);
+
+ALTER TABLE games ADD COLUMN IF NOT EXISTS playgroup_id INTEGER REFERENCES playgroups(id);
+UPDATE games SET playgroup_id = (SELECT id FROM playgroups WHERE slug = 'default') WHERE playgroup_id IS NULL;
+ALTER TABLE games ALTER COLUMN playgroup_id SET NOT NULL;
+
+ALTER TABLE seasons ADD COLUMN IF NOT EXISTS playgroup_id INTEGER REFERENCES playgroups(id);
+UPDATE seasons SET playgroup_id = (SELECT id FROM playgroups WHERE slug = 'default') WHERE playgroup_id IS NULL;
+ALTER TABLE seasons ALTER COLUMN playgroup_id SET NOT NULL;
+ALTER TABLE seasons DROP CONSTRAINT IF EXISTS seasons_name_key;
+CREATE UNIQUE INDEX IF NOT EXISTS seasons_playgroup_id_name_key ON seasons (playgroup_id, name);
+
+ALTER TABLE scoring_schemes ADD COLUMN IF NOT EXISTS playgroup_id INTEGER REFERENCES playgroups(id);
+UPDATE scoring_schemes SET playgroup_id = (SELECT id FROM playgroups WHERE slug = 'default') WHERE playgroup_id IS NULL;
+ALTER TABLE scoring_schemes ALTER COLUMN playgroup_id SET NOT NULL;
+ALTER TABLE scoring_schemes DROP CONSTRAINT IF EXISTS scoring_schemes_name_key;
+CREATE UNIQUE INDEX IF NOT EXISTS scoring_schemes_playgroup_id_name_key ON scoring_schemes (playgroup_id, name);
@@ -0,0 +1,33 @@
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    username TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    admin BOOLEAN NOT NULL DEFAULT FALSE,
+    revoked_at TIMESTAMP WITH TIME ZONE
+);
+
+-- Only a hash of the token is kept, see accounts::hash_token
+CREATE TABLE IF NOT EXISTS sessions (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    token_hash TEXT UNIQUE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS playgroup_members (
+    playgroup_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    PRIMARY KEY (playgroup_id, user_id),
+    FOREIGN KEY (playgroup_id) REFERENCES playgroups(id),
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+-- Who entered what, NULL for anything from before accounts
+ALTER TABLE players ADD COLUMN IF NOT EXISTS created_by INTEGER REFERENCES users(id);
+ALTER TABLE games ADD COLUMN IF NOT EXISTS submitted_by INTEGER REFERENCES users(id);
+ALTER TABLE games ADD COLUMN IF NOT EXISTS updated_by INTEGER REFERENCES users(id);
+
+-- Playgroups had a shared write token of their own before there were accounts
+ALTER TABLE playgroups DROP COLUMN IF EXISTS token_hash;
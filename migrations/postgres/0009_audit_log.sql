@@ -0,0 +1,15 @@
+-- before and after are snapshots of the player or game, written in the
+-- same transaction as the change itself, see record in store/sql.rs
+CREATE TABLE IF NOT EXISTS audit_log (
+    id SERIAL PRIMARY KEY,
+    playgroup_id INTEGER NOT NULL,
+    actor_id INTEGER,
+    entity TEXT NOT NULL,
+    entity_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    before JSONB,
+    after JSONB,
+    FOREIGN KEY (playgroup_id) REFERENCES playgroups(id),
+    FOREIGN KEY (actor_id) REFERENCES users(id)
+);
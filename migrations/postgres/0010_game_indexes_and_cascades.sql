@@ -0,0 +1,26 @@
+-- Every game lookup joins through these, without indexes they were full table scans
+CREATE INDEX IF NOT EXISTS games_players_game_id_idx ON games_players (game_id);
+CREATE INDEX IF NOT EXISTS games_players_player_id_idx ON games_players (player_id);
+CREATE INDEX IF NOT EXISTS commanders_games_players_id_idx ON commanders (games_players_id);
+
+-- Removing a game or player takes the rows that only exist because of it along.
+--
+-- The foreign keys being replaced were declared without names, both here and
+-- by main() before there were migrations, so they have postgres' default
+-- <table>_<column>_fkey names. A database where they were renamed by hand
+-- would keep its old constraint next to the new one.
+ALTER TABLE games_players
+    DROP CONSTRAINT IF EXISTS games_players_game_id_fkey,
+    ADD CONSTRAINT games_players_game_id_fkey FOREIGN KEY (game_id) REFERENCES games(id) ON DELETE CASCADE,
+    DROP CONSTRAINT IF EXISTS games_players_player_id_fkey,
+    ADD CONSTRAINT games_players_player_id_fkey FOREIGN KEY (player_id) REFERENCES players(id) ON DELETE CASCADE;
+
+ALTER TABLE commanders
+    DROP CONSTRAINT IF EXISTS commanders_games_players_id_fkey,
+    ADD CONSTRAINT commanders_games_players_id_fkey FOREIGN KEY (games_players_id) REFERENCES games_players(id) ON DELETE CASCADE;
+
+ALTER TABLE ratings
+    DROP CONSTRAINT IF EXISTS ratings_game_id_fkey,
+    ADD CONSTRAINT ratings_game_id_fkey FOREIGN KEY (game_id) REFERENCES games(id) ON DELETE CASCADE,
+    DROP CONSTRAINT IF EXISTS ratings_player_id_fkey,
+    ADD CONSTRAINT ratings_player_id_fkey FOREIGN KEY (player_id) REFERENCES players(id) ON DELETE CASCADE;
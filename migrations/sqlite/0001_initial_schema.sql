@@ -0,0 +1,126 @@
+-- The same schema as migrations/postgres after all of its migrations.
+-- Datetimes are kept as RFC 3339 text in UTC so they sort and compare
+-- as text, and what postgres keeps as arrays or JSONB is JSON text.
+
+CREATE TABLE playgroups (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    slug TEXT UNIQUE NOT NULL,
+    name TEXT NOT NULL
+);
+
+INSERT INTO playgroups (slug, name) VALUES ('default', 'Default');
+
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    admin BOOLEAN NOT NULL DEFAULT FALSE,
+    revoked_at TEXT
+);
+
+-- Only a hash of the token is kept, see accounts::hash_token
+CREATE TABLE sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    token_hash TEXT UNIQUE NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL
+);
+
+CREATE TABLE playgroup_members (
+    playgroup_id INTEGER NOT NULL REFERENCES playgroups(id),
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    PRIMARY KEY (playgroup_id, user_id)
+);
+
+-- Tokens for scripts, scopes is a JSON array of Scope::as_str values
+CREATE TABLE api_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    name TEXT NOT NULL,
+    token_hash TEXT UNIQUE NOT NULL,
+    scopes TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT,
+    revoked_at TEXT
+);
+
+-- before and after are JSON snapshots of the player or game
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    playgroup_id INTEGER NOT NULL REFERENCES playgroups(id),
+    actor_id INTEGER REFERENCES users(id),
+    entity TEXT NOT NULL,
+    entity_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    before TEXT,
+    after TEXT
+);
+
+CREATE TABLE players (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    playgroup_id INTEGER NOT NULL REFERENCES playgroups(id),
+    name TEXT NOT NULL,
+    created_by INTEGER REFERENCES users(id)
+);
+
+CREATE UNIQUE INDEX players_playgroup_id_name_key ON players (playgroup_id, name);
+
+-- Deleted games keep their rows so they can be restored
+CREATE TABLE games (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    playgroup_id INTEGER NOT NULL REFERENCES playgroups(id),
+    start_datetime TEXT NOT NULL,
+    end_datetime TEXT NOT NULL,
+    deleted_at TEXT,
+    submitted_by INTEGER REFERENCES users(id),
+    updated_by INTEGER REFERENCES users(id)
+);
+
+CREATE TABLE games_players (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    game_id INTEGER NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+    player_id INTEGER NOT NULL REFERENCES players(id) ON DELETE CASCADE,
+    rank INTEGER NOT NULL
+);
+
+CREATE INDEX games_players_game_id_idx ON games_players (game_id);
+CREATE INDEX games_players_player_id_idx ON games_players (player_id);
+
+CREATE TABLE commanders (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    games_players_id INTEGER NOT NULL REFERENCES games_players(id) ON DELETE CASCADE,
+    commander TEXT NOT NULL
+);
+
+CREATE INDEX commanders_games_players_id_idx ON commanders (games_players_id);
+
+CREATE TABLE seasons (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    playgroup_id INTEGER NOT NULL REFERENCES playgroups(id),
+    name TEXT NOT NULL,
+    start_datetime TEXT NOT NULL,
+    end_datetime TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX seasons_playgroup_id_name_key ON seasons (playgroup_id, name);
+
+-- definition is JSON, see scoring::SchemeDefinition
+CREATE TABLE scoring_schemes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    playgroup_id INTEGER NOT NULL REFERENCES playgroups(id),
+    name TEXT NOT NULL,
+    definition TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX scoring_schemes_playgroup_id_name_key ON scoring_schemes (playgroup_id, name);
+
+-- Derived from the games, see ratings::compute_ratings
+CREATE TABLE ratings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    game_id INTEGER NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+    player_id INTEGER NOT NULL REFERENCES players(id) ON DELETE CASCADE,
+    rating_before REAL NOT NULL,
+    rating_after REAL NOT NULL
+);
@@ -1,21 +1,30 @@
 use chrono::Duration;
 use gloo_net::http::Request;
-use wasm_bindgen_futures;
 use gloo_console::log;
 use web_sys::HtmlInputElement;
 use wasm_bindgen::JsCast;
 use gloo_timers::callback::Timeout;
-use chrono::{Local, NaiveDateTime, DurationRound, TimeZone};
-use ormos::messages::*;
+use chrono::{Local, NaiveDateTime, DurationRound, TimeZone, Utc};
+use ormos::{messages::*, validation};
 use yew_hooks::prelude::*;
 use crate::components::toast::*;
 use crate::components::player_select::*;
 use crate::components::rank_select::*;
 use crate::components::commander_input::*;
 use crate::components::player_data::*;
+use crate::components::game_list::*;
+use crate::components::login_form::*;
 use yew::prelude::*;
 
-fn create_message(messages: UseListHandle<String>, message: String) {
+fn create_message(messages: UseListHandle<ToastMessage>, message: String) {
+    push_message(messages, ToastMessage { text: message, undo: None });
+}
+
+fn create_undo_message(messages: UseListHandle<ToastMessage>, message: String, undo: Callback<()>) {
+    push_message(messages, ToastMessage { text: message, undo: Some(undo) });
+}
+
+fn push_message(messages: UseListHandle<ToastMessage>, message: ToastMessage) {
     messages.push(message);
     Timeout::new(5000, move || {
         messages.remove(0);
@@ -31,7 +40,7 @@ enum GameTime {
 #[function_component(App)]
 pub fn app() -> Html {
 
-    let players = use_state(|| Vec::new());
+    let players = use_state(Vec::new);
     {
         let players = players.clone();
         use_effect_with((), move |_| {
@@ -62,7 +71,7 @@ pub fn app() -> Html {
 
     let selected_players = use_state(|| ["".to_string(), "".to_string(), "".to_string(), "".to_string()]);
 
-    let num_selected_players = selected_players.iter().filter(|player| *player != "").count();
+    let num_selected_players = selected_players.iter().filter(|player| !player.is_empty()).count();
 
     let on_player_select = |index: usize| {
         let selected_players = selected_players.clone();
@@ -78,7 +87,7 @@ pub fn app() -> Html {
     let select_rank_callback = |index: usize| {
         let selected_ranks = selected_ranks.clone();
         Callback::from(move |rank: usize|{
-            let mut copy = (*selected_ranks).clone();
+            let mut copy = *selected_ranks;
             copy[index] = rank;
             selected_ranks.set(copy);
         })
@@ -107,6 +116,17 @@ pub fn app() -> Html {
     };
 
     // "%Y-%m-%dT%H:%M"
+    // For cards too new to be in the server's commander list
+    let allow_unknown_commanders = use_state(|| false);
+
+    let on_allow_unknown_commanders = {
+        let allow_unknown_commanders = allow_unknown_commanders.clone();
+        Callback::from(move |event: Event| {
+            let input: HtmlInputElement = event.target_unchecked_into();
+            allow_unknown_commanders.set(input.checked());
+        })
+    };
+
     let start_datetime = use_state(|| Local::now().duration_round(Duration::minutes(1)).unwrap());
     let end_datetime = use_state(|| Local::now().duration_round(Duration::minutes(1)).unwrap());
 
@@ -141,54 +161,68 @@ pub fn app() -> Html {
         })
     };
 
+    let add_undo_message = {
+        let messages = messages.clone();
+        Callback::from(move |(m, undo): (String, Callback<()>)| {
+            create_undo_message(messages.clone(), m, undo);
+        })
+    };
+
     let token = use_state(|| String::from(""));
 
-    let token_oninput = {
+    let token_callback = {
         let token = token.clone();
-        Callback::from(move |event: InputEvent| {
-            let token = token.clone();
-            let input_event_target = event.target().unwrap();
-            let current_input_text = input_event_target.unchecked_into::<HtmlInputElement>().value();
-
-            token.set(current_input_text);
+        Callback::from(move |new_token: String| {
+            token.set(new_token);
         })
     };
 
 
-    let on_game_submit = {
-        let messages = messages.clone();
-        let commander_inputs = commander_inputs.clone();
-        let selected_players = selected_players.clone();
-        let selected_ranks = selected_ranks.clone();
-        let start_datetime = start_datetime.clone();
-        let end_datetime = end_datetime.clone();
+    let payload = {
         let mut players: Vec<Player> = Vec::new();
-        let token = token.clone();
         for index in 0..4 {
-            if selected_players[index] != "" {
+            if !selected_players[index].is_empty() {
                 let mut commanders = Vec::new();
-                if commander_inputs[index] != "" {
+                if !commander_inputs[index].is_empty() {
                     commanders.push(commander_inputs[index].clone());
                 }
 
-                if partner_inputs[index] != "" {
+                if !partner_inputs[index].is_empty() {
                     commanders.push(partner_inputs[index].clone());
                 }
 
                 players.push(
                     Player{
+                        id: None,
                         commanders,
                         name: selected_players[index].clone(),
-                        rank: selected_ranks[index].clone()
+                        rank: selected_ranks[index]
                 });
             }
         }
 
-        let payload = CreateGamePayload{
-            start_datetime: start_datetime.to_rfc3339(),
-            end_datetime: end_datetime.to_rfc3339(),
+        CreateGamePayload{
+            start_datetime: start_datetime.with_timezone(&Utc),
+            end_datetime: end_datetime.with_timezone(&Utc),
             players
-        };
+        }
+    };
+
+    // The same checks the server runs, shown next to the form as it's filled in
+    let game_errors = validation::game_errors(&payload);
+
+    let player_errors = |index: usize| {
+        game_errors
+            .iter()
+            .filter(|error| !selected_players[index].is_empty() && error.player() == Some(selected_players[index].as_str()))
+            .map(|error| html! { <div class="form-error">{ error.to_string() }</div> })
+            .collect::<Html>()
+    };
+
+    let on_game_submit = {
+        let messages = messages.clone();
+        let token = token.clone();
+        let allow_unknown_commanders = *allow_unknown_commanders;
 
         Callback::from(move |_| {
             let messages = messages.clone();
@@ -198,6 +232,7 @@ pub fn app() -> Html {
 
             wasm_bindgen_futures::spawn_local(async move {
                 let response: PostResponse = Request::post("/api/games")
+                    .query([("allow_unknown_commanders", allow_unknown_commanders.to_string())])
                     .header("Authorization", format!("Bearer {}", token.as_str()).as_str())
                     .json(&payload)
                     .unwrap()
@@ -217,7 +252,7 @@ pub fn app() -> Html {
         })
     };
 
-    let commanders = use_state(|| Vec::new());
+    let commanders = use_state(Vec::new);
 
     {
         let commanders = commanders.clone();
@@ -238,8 +273,7 @@ pub fn app() -> Html {
 
     html! {
         <main>
-            <label>{"Password"}</label>
-            <input type="password" oninput={token_oninput}/>
+            <LoginForm token={(*token).clone()} token_callback={token_callback} message_callback={add_message.clone()}/>
             <table>
                 <tr>
                     <td><label>{ "Start time" }</label></td>
@@ -267,38 +301,52 @@ pub fn app() -> Html {
                     <td><label>{ "Rank" }</label></td>
                 </tr>
                 <tr>
-                    <td><PlayersSelect players={(*players).clone()} select_callback={on_player_select.clone()(0)}/></td>
-                    <td><CommanderInput onchange={on_commander_input.clone()(0)}/></td>
-                    <td><CommanderInput onchange={on_partnet_input.clone()(0)}/></td>
-                    <td><RankSelect select_callback={select_rank_callback.clone()(0)} num_players={num_selected_players.clone()}/></td>
+                    <td><PlayersSelect players={(*players).clone()} select_callback={on_player_select(0)}/></td>
+                    <td><CommanderInput onchange={on_commander_input(0)}/></td>
+                    <td><CommanderInput onchange={on_partnet_input(0)}/></td>
+                    <td><RankSelect select_callback={select_rank_callback(0)} num_players={num_selected_players}/></td>
+                    <td>{ player_errors(0) }</td>
                 </tr>
                 <tr>
-                    <td><PlayersSelect players={(*players).clone()} select_callback={on_player_select.clone()(1)}/></td>
-                    <td><CommanderInput onchange={on_commander_input.clone()(1)}/></td>
-                    <td><CommanderInput onchange={on_partnet_input.clone()(1)}/></td>
-                    <td><RankSelect select_callback={select_rank_callback.clone()(1)} num_players={num_selected_players.clone()}/></td>
+                    <td><PlayersSelect players={(*players).clone()} select_callback={on_player_select(1)}/></td>
+                    <td><CommanderInput onchange={on_commander_input(1)}/></td>
+                    <td><CommanderInput onchange={on_partnet_input(1)}/></td>
+                    <td><RankSelect select_callback={select_rank_callback(1)} num_players={num_selected_players}/></td>
+                    <td>{ player_errors(1) }</td>
                 </tr>
                 <tr>
-                    <td><PlayersSelect players={(*players).clone()} select_callback={on_player_select.clone()(2)}/></td>
-                    <td><CommanderInput onchange={on_commander_input.clone()(2)}/></td>
-                    <td><CommanderInput onchange={on_partnet_input.clone()(2)}/></td>
-                    <td><RankSelect select_callback={select_rank_callback.clone()(2)} num_players={num_selected_players.clone()}/></td>
+                    <td><PlayersSelect players={(*players).clone()} select_callback={on_player_select(2)}/></td>
+                    <td><CommanderInput onchange={on_commander_input(2)}/></td>
+                    <td><CommanderInput onchange={on_partnet_input(2)}/></td>
+                    <td><RankSelect select_callback={select_rank_callback(2)} num_players={num_selected_players}/></td>
+                    <td>{ player_errors(2) }</td>
                 </tr>
                 <tr>
-                    <td><PlayersSelect players={(*players).clone()} select_callback={on_player_select.clone()(3)}/></td>
-                    <td><CommanderInput onchange={on_commander_input.clone()(3)}/></td>
-                    <td><CommanderInput onchange={on_partnet_input.clone()(3)}/></td>
-                    <td><RankSelect select_callback={select_rank_callback.clone()(3)} num_players={num_selected_players.clone()}/></td>
+                    <td><PlayersSelect players={(*players).clone()} select_callback={on_player_select(3)}/></td>
+                    <td><CommanderInput onchange={on_commander_input(3)}/></td>
+                    <td><CommanderInput onchange={on_partnet_input(3)}/></td>
+                    <td><RankSelect select_callback={select_rank_callback(3)} num_players={num_selected_players}/></td>
+                    <td>{ player_errors(3) }</td>
                 </tr>
             </table>
-            <button onclick={on_game_submit.clone()}>{"Submit"}</button>
+            {
+                game_errors.iter().filter(|error| error.player().is_none()).map(|error| {
+                    html! { <div class="form-error">{ error.to_string() }</div> }
+                }).collect::<Html>()
+            }
+            <label>
+                <input type="checkbox" checked={*allow_unknown_commanders} onchange={on_allow_unknown_commanders}/>
+                { "Allow commanders that aren't in the list" }
+            </label>
+            <button onclick={on_game_submit.clone()} disabled={!game_errors.is_empty()}>{"Submit"}</button>
             <br/>
-            <NewPlayerForm token={(*token).clone()} players_update_callback={player_update_callback.clone()} message_callback={add_message}/>
+            <NewPlayerForm token={(*token).clone()} players_update_callback={player_update_callback.clone()} message_callback={add_message.clone()}/>
+            <GameList token={(*token).clone()} message_callback={add_message} undo_message_callback={add_undo_message}/>
             <div class="toast-container">
                 {
                     messages.current().iter().map(|message| {
                         html!{
-                            <Toast message={message.to_string()}/>
+                            <Toast message={message.text.clone()} undo={message.undo.clone()}/>
                         }
                     }).collect::<Html>()
                 }
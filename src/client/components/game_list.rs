@@ -0,0 +1,141 @@
+use gloo_net::http::Request;
+use chrono::Local;
+use ormos::messages::{Game, GamesResponse, PostResponse};
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub token: String,
+    pub message_callback: Callback<String>,
+    pub undo_message_callback: Callback<(String, Callback<()>)>
+}
+
+fn fetch_games(games: UseStateHandle<Vec<Game>>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        // The server sends games newest first
+        let fetched_games: GamesResponse = Request::get("/api/games")
+            .query([("limit", "20")])
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        games.set(fetched_games.games);
+    });
+}
+
+fn restore_game(id: i32, token: String, games: UseStateHandle<Vec<Game>>, message_callback: Callback<String>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        let response: Result<PostResponse, gloo_net::Error> = Request::post(&format!("/api/games/{}/restore", id))
+            .header("Authorization", format!("Bearer {}", token.as_str()).as_str())
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await;
+
+        match response {
+            Ok(response) => {
+                if response.success {
+                    fetch_games(games);
+                }
+                else {
+                    message_callback.emit(response.error.unwrap());
+                }
+            },
+            Err(error) => {
+                message_callback.emit(format!("Server sent data we couldn't deserialze. Error was: {}", error));
+            }
+        }
+    });
+}
+
+#[function_component(GameList)]
+pub fn game_list(Props{ token, message_callback, undo_message_callback }: &Props) -> Html {
+    let games = use_state(Vec::new);
+
+    {
+        let games = games.clone();
+        use_effect_with((), move |_| {
+            fetch_games(games);
+        });
+    }
+
+    let on_delete = |id: i32| {
+        let token = token.clone();
+        let games = games.clone();
+        let message_callback = message_callback.clone();
+        let undo_message_callback = undo_message_callback.clone();
+
+        Callback::from(move |_| {
+            let token = token.clone();
+            let games = games.clone();
+            let message_callback = message_callback.clone();
+            let undo_message_callback = undo_message_callback.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let response: Result<PostResponse, gloo_net::Error> = Request::delete(&format!("/api/games/{}", id))
+                    .header("Authorization", format!("Bearer {}", token.as_str()).as_str())
+                    .send()
+                    .await
+                    .unwrap()
+                    .json()
+                    .await;
+
+                match response {
+                    Ok(response) => {
+                        if response.success {
+                            let undo = {
+                                let games = games.clone();
+                                let message_callback = message_callback.clone();
+                                Callback::from(move |_| {
+                                    restore_game(id, token.clone(), games.clone(), message_callback.clone());
+                                })
+                            };
+
+                            undo_message_callback.emit((format!("Game {} deleted", id), undo));
+                            fetch_games(games);
+                        }
+                        else {
+                            message_callback.emit(response.error.unwrap());
+                        }
+                    },
+                    Err(error) => {
+                        message_callback.emit(format!("Server sent data we couldn't deserialze. Error was: {}", error));
+                    }
+                }
+            });
+        })
+    };
+
+    html!{
+        <table class="game-list">
+            <tr>
+                <td><label>{ "Played" }</label></td>
+                <td><label>{ "Players" }</label></td>
+                <td></td>
+            </tr>
+            {
+                games.iter().map(|game| {
+                    let mut players = game.players.clone();
+                    players.sort_by_key(|player| player.rank);
+                    let players = players
+                        .iter()
+                        .map(|player| format!("{}. {} ({})", player.rank, player.name, player.commanders.join(" / ")))
+                        .collect::<Vec<String>>()
+                        .join(", ");
+
+                    html!{
+                        <tr key={game.id}>
+                            <td>{ game.start_datetime.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string() }</td>
+                            <td>{ players }</td>
+                            <td><button onclick={on_delete(game.id)}>{"Delete"}</button></td>
+                        </tr>
+                    }
+                }).collect::<Html>()
+            }
+        </table>
+    }
+}
@@ -0,0 +1,118 @@
+use gloo_net::http::Request;
+use web_sys::HtmlInputElement;
+use wasm_bindgen::JsCast;
+use ormos::messages::{ErrorResponse, LoginPayload, LoginResponse};
+use yew::prelude::*;
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub token: String,
+    // Emits the session token after logging in and an empty string after logging out
+    pub token_callback: Callback<String>,
+    pub message_callback: Callback<String>
+}
+
+fn input_value(event: InputEvent) -> String {
+    event.target().unwrap().unchecked_into::<HtmlInputElement>().value()
+}
+
+#[function_component(LoginForm)]
+pub fn login_form(Props{ token, token_callback, message_callback }: &Props) -> Html {
+    let username = use_state(|| String::from(""));
+    let password = use_state(|| String::from(""));
+    let logged_in_as = use_state(|| String::from(""));
+
+    let username_oninput = {
+        let username = username.clone();
+        Callback::from(move |event: InputEvent| username.set(input_value(event)))
+    };
+
+    let password_oninput = {
+        let password = password.clone();
+        Callback::from(move |event: InputEvent| password.set(input_value(event)))
+    };
+
+    let on_login = {
+        let username = username.clone();
+        let password = password.clone();
+        let logged_in_as = logged_in_as.clone();
+        let token_callback = token_callback.clone();
+        let message_callback = message_callback.clone();
+
+        Callback::from(move |_| {
+            let payload = LoginPayload {
+                username: (*username).clone(),
+                password: (*password).clone()
+            };
+            let password = password.clone();
+            let logged_in_as = logged_in_as.clone();
+            let token_callback = token_callback.clone();
+            let message_callback = message_callback.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                let response = Request::post("/api/login")
+                    .json(&payload)
+                    .unwrap()
+                    .send()
+                    .await
+                    .unwrap();
+
+                if response.ok() {
+                    let login: LoginResponse = response.json().await.unwrap();
+                    password.set(String::from(""));
+                    logged_in_as.set(login.username);
+                    token_callback.emit(login.token);
+                }
+                else {
+                    match response.json::<ErrorResponse>().await {
+                        Ok(error) => message_callback.emit(error.error),
+                        Err(error) => message_callback.emit(format!("Server sent data we couldn't deserialze. Error was: {}", error))
+                    }
+                }
+            });
+        })
+    };
+
+    let on_logout = {
+        let token = token.clone();
+        let logged_in_as = logged_in_as.clone();
+        let token_callback = token_callback.clone();
+
+        Callback::from(move |_| {
+            let token = token.clone();
+            let logged_in_as = logged_in_as.clone();
+            let token_callback = token_callback.clone();
+
+            wasm_bindgen_futures::spawn_local(async move {
+                // Forget the token even if the server couldn't be reached
+                let _ = Request::post("/api/logout")
+                    .header("Authorization", format!("Bearer {}", token.as_str()).as_str())
+                    .send()
+                    .await;
+
+                logged_in_as.set(String::from(""));
+                token_callback.emit(String::from(""));
+            });
+        })
+    };
+
+    if token.is_empty() {
+        html!{
+            <div class="login-form">
+                <label>{"Username"}</label>
+                <input oninput={username_oninput} value={(*username).clone()}/>
+                <label>{"Password"}</label>
+                <input type="password" oninput={password_oninput} value={(*password).clone()}/>
+                <button onclick={on_login}>{"Log in"}</button>
+            </div>
+        }
+    }
+    else {
+        html!{
+            <div class="login-form">
+                <label>{ format!("Logged in as {}", *logged_in_as) }</label>
+                <button onclick={on_logout}>{"Log out"}</button>
+            </div>
+        }
+    }
+}
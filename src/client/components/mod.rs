@@ -1,5 +1,7 @@
 pub mod player_select;
 pub mod commander_input;
 pub mod player_data;
+pub mod game_list;
+pub mod login_form;
 pub mod rank_select;
 pub mod toast;
@@ -68,7 +68,7 @@ pub fn new_player_form(Props{ token, players_update_callback, message_callback }
                         }
                     },
                     Err(error) => {
-                        message_callback.emit(format!("Server sent data we couldn't deserialze. Error was: {}", error.to_string()));
+                        message_callback.emit(format!("Server sent data we couldn't deserialze. Error was: {}", error));
                     }
                 }
             })
@@ -28,7 +28,7 @@ pub fn rank_select(Props{ num_players, select_callback }: &Props) -> Html {
             {
                 (0..*num_players).map(|x| {
                     html! {
-                        <option key={x+1} value={(x+1).to_string()}>{(x+1).clone()}</option>
+                        <option key={x+1} value={(x+1).to_string()}>{x+1}</option>
                     }
                 }).collect::<Html>()
             }
@@ -1,13 +1,22 @@
 use yew::prelude::*;
 
+#[derive(Clone, PartialEq)]
+pub struct ToastMessage {
+    pub text: String,
+    // When set the toast shows an "Undo" button that emits this
+    pub undo: Option<Callback<()>>
+}
+
 #[derive(Properties, PartialEq)]
 pub struct Props {
     pub message: AttrValue,
+    #[prop_or_default]
+    pub undo: Option<Callback<()>>,
 }
 
 
 #[function_component(Toast)]
-pub fn toast(Props{ message }: &Props) -> Html {
+pub fn toast(Props{ message, undo }: &Props) -> Html {
 
     let hidden = use_state(|| false);
 
@@ -18,7 +27,23 @@ pub fn toast(Props{ message }: &Props) -> Html {
         })
     };
 
+    let undo_button = undo.clone().map(|undo| {
+        let on_undo = {
+            let hidden = hidden.clone();
+            Callback::from(move |event: MouseEvent| {
+                // Don't let the click fall through to the toast itself
+                event.stop_propagation();
+                hidden.set(true);
+                undo.emit(());
+            })
+        };
+
+        html! {
+            <button class="toast-undo" onclick={on_undo}>{"Undo"}</button>
+        }
+    });
+
     html! {
-        <div onclick={on_click.clone()} hidden={*hidden} class="toast" >{message}</div>
+        <div onclick={on_click.clone()} hidden={*hidden} class="toast" >{message}{undo_button}</div>
     }
 }
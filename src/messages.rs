@@ -1,5 +1,6 @@
 use serde::{Serialize, Deserialize};
 use chrono::{DateTime, Utc};
+use std::{collections::BTreeMap, fmt};
 
 #[derive(Serialize, Deserialize)]
 pub struct PlayersResponse{
@@ -24,6 +25,10 @@ pub struct PostResponse {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Player {
+    // Always set in responses, clients don't need
+    // to send it when creating a game
+    #[serde(default)]
+    pub id: Option<i32>,
     pub name: String,
     pub commanders: Vec<String>,
     pub rank: usize
@@ -31,14 +36,53 @@ pub struct Player {
 
 #[derive(Serialize, Deserialize)]
 pub struct GamesResponse {
-    pub games: Vec<Game>
+    pub games: Vec<Game>,
+    // Pass as `before` to get the next page, None when there are no more games
+    #[serde(default)]
+    pub next_cursor: Option<i32>
+}
+
+// Query parameters for POST, PUT and PATCH on games
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GameWriteQuery {
+    // Take commanders that aren't in the list from Scryfall, e.g. cards too new to be in it
+    #[serde(default)]
+    pub allow_unknown_commanders: bool,
+}
+
+// Query parameters for GET /api/games, every one of them is optional
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GamesQuery {
+    // Only games that started at or after this
+    pub from: Option<DateTime<Utc>>,
+    // Only games that started before this
+    pub to: Option<DateTime<Utc>>,
+    // Only games this player was in
+    pub player: Option<String>,
+    // Only games where someone played this commander
+    pub commander: Option<String>,
+    // Only games with exactly this many players
+    pub players: Option<usize>,
+    // Only games that come after this game ID in newest first order
+    pub before: Option<i32>,
+    // GET /api/games never hands out more than 100 games at once
+    pub limit: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize)]
+pub struct GameResponse {
+    pub game: Game
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Game {
+    pub id: i32,
     pub start_datetime: DateTime<Utc>,
     pub end_datetime: DateTime<Utc>,
     pub players: Vec<Player>,
+    // Username of whoever entered the game, None for games from before accounts
+    #[serde(default)]
+    pub submitted_by: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -48,13 +92,699 @@ pub struct PlayerPayload {
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct CreateGamePayload {
-    pub start_datetime: String,
-    pub end_datetime: String,
+    pub start_datetime: DateTime<Utc>,
+    pub end_datetime: DateTime<Utc>,
     pub players: Vec<Player>,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct BearerAuthFailureResponse {
+// Fields left as None keep their current value
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UpdateGamePayload {
+    pub start_datetime: Option<DateTime<Utc>>,
+    pub end_datetime: Option<DateTime<Utc>>,
+    pub players: Option<Vec<Player>>,
+}
+
+// Everything the API can refuse a request with. code is stable for clients
+// to match on and the fields say what exactly was wrong, e.g.
+// {"code": "UNKNOWN_PLAYER", "player": "bob"}
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "code", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ApiError {
+    // Games, see validation::GameError
+    TooFewPlayers,
+    DuplicatePlayer { player: String },
+    InvalidDateRange,
+    InvalidRank { player: String, rank: usize },
+    NoFirstPlace,
+    InvalidRankSequence { rank: usize, expected: usize, previous: usize },
+    NoCommanders { player: String },
+    EmptyCommander { player: String },
+    TooManyCommanders { player: String },
+    UnknownPlayer { player: String },
+    // Not in the server's commander list, suggestions are the closest names that are
+    UnknownCommander { player: String, commander: String, suggestions: Vec<String> },
+    // Two commanders whose pairing abilities don't allow playing them together
+    IllegalPair { player: String, commanders: Vec<String>, reason: String },
+    GameNotFound { id: i32 },
+    GameNotDeleted { id: i32 },
+    // The before cursor isn't a live game in the playgroup
+    UnknownCursor { before: i32 },
+
+    PlayerExists { player: String },
+    PlayerNotFound { player: String },
+    CommanderNotFound { commander: String },
+    TooFewHeadToHeadPlayers,
+
+    MissingToken,
+    MalformedToken,
+    InvalidToken,
+    MissingScope { scope: Scope },
+    NotAMember { username: String, playgroup: String },
+    AdminOnly,
+    // API tokens can't be used to make or manage other API tokens
+    LoginRequired,
+    IncorrectLogin,
+
+    // A required field was left empty
+    MissingField { field: String },
+    UserExists { username: String },
+    UserNotFound { username: String },
+    CannotRevokeSelf,
+    MemberNotFound { username: String, playgroup: String },
+
+    ScopeNotAllowed { scope: Scope },
+    ExpiryInPast,
+    ApiTokenNotFound { id: i32 },
+
+    InvalidSlug { slug: String },
+    PlaygroupExists { slug: String },
+    PlaygroupNotFound { slug: String },
+
+    SeasonExists { name: String },
+    SeasonNotFound { id: i32 },
+
+    NoPointTables,
+    InvalidPodSize { players: usize },
+    WrongPlaceCount { players: usize, places: usize },
+    InvalidPoints { players: usize },
+    SchemeExists { name: String },
+    SchemeNotFound { id: i32 },
+
+    // Requests the server couldn't make sense of, message says what was wrong
+    InvalidJson { message: String },
+    MissingJsonContentType,
+    PayloadTooLarge,
+    InvalidQuery { message: String },
+    InvalidPath { message: String },
+
+    // The server couldn't reach its database, trying again later may work
+    DatabaseUnavailable,
+    // The server hasn't finished downloading the commanders from Scryfall yet
+    CommandersUnavailable,
+    // Something broke on the server, nothing the client can fix
+    Internal,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::TooFewPlayers => write!(f, "A game must have at least two players"),
+            ApiError::DuplicatePlayer { .. } => write!(f, "Cannot have the same player multiple times"),
+            ApiError::InvalidDateRange => write!(f, "End datetime cannot be earlier than or equal to start datetime"),
+            ApiError::InvalidRank { player, .. } => write!(f, "Player {} has invalid value for rank", player),
+            ApiError::NoFirstPlace => write!(f, "At least one player must come in first"),
+            ApiError::InvalidRankSequence { rank, expected, previous } => write!(f, "Ranking is invalid player with a rank {} should have rank {} or {}", rank, expected, previous),
+            ApiError::NoCommanders { player } => write!(f, "Player \"{}\" has no commanders", player),
+            ApiError::EmptyCommander { player } => write!(f, "Player \"{}\" has an empty string as a commander", player),
+            ApiError::TooManyCommanders { player } => write!(f, "Player \"{}\" has more than two commanders", player),
+            ApiError::UnknownPlayer { player } => write!(f, "Player \"{}\" does not exist", player),
+            ApiError::UnknownCommander { player, commander, suggestions } if suggestions.is_empty() => {
+                write!(f, "Player \"{}\" has unknown commander \"{}\"", player, commander)
+            },
+            ApiError::UnknownCommander { player, commander, suggestions } => {
+                let suggestions: Vec<String> = suggestions.iter().map(|suggestion| format!("\"{}\"", suggestion)).collect();
+                write!(f, "Player \"{}\" has unknown commander \"{}\", did you mean {}?", player, commander, suggestions.join(", "))
+            },
+            ApiError::IllegalPair { player, reason, .. } => write!(f, "Player \"{}\" has commanders that can't be played together, {}", player, reason),
+            ApiError::GameNotFound { id } => write!(f, "Game {} does not exist", id),
+            ApiError::UnknownCursor { before } => write!(f, "Cursor {} is not a game in this playgroup", before),
+            ApiError::GameNotDeleted { id } => write!(f, "Game {} is not deleted", id),
+            ApiError::PlayerExists { player } => write!(f, "Player \"{}\" already exists", player),
+            ApiError::PlayerNotFound { player } => write!(f, "Player \"{}\" does not exist", player),
+            ApiError::CommanderNotFound { commander } => write!(f, "No games have been played with \"{}\"", commander),
+            ApiError::TooFewHeadToHeadPlayers => write!(f, "Head to head needs at least two different players"),
+            ApiError::MissingToken => write!(f, "Missing bearer token in Authorization header."),
+            ApiError::MalformedToken => write!(f, "Invalid bearer token."),
+            ApiError::InvalidToken => write!(f, "Incorrect or expired bearer token provided."),
+            ApiError::MissingScope { scope } => write!(f, "Bearer token is missing the {} scope.", scope.as_str()),
+            ApiError::NotAMember { username, playgroup } => write!(f, "{} is not a member of {}", username, playgroup),
+            ApiError::AdminOnly => write!(f, "Only admins can do that"),
+            ApiError::LoginRequired => write!(f, "API tokens can only be managed after logging in with a password"),
+            ApiError::IncorrectLogin => write!(f, "Incorrect username or password"),
+            ApiError::MissingField { field } => write!(f, "\"{}\" can't be empty", field),
+            ApiError::UserExists { username } => write!(f, "User \"{}\" already exists", username),
+            ApiError::UserNotFound { username } => write!(f, "User \"{}\" does not exist", username),
+            ApiError::CannotRevokeSelf => write!(f, "You can't revoke your own account"),
+            ApiError::MemberNotFound { username, playgroup } => write!(f, "User \"{}\" is not a member of {}", username, playgroup),
+            ApiError::ScopeNotAllowed { scope } => write!(f, "You can't create a token with the {} scope", scope.as_str()),
+            ApiError::ExpiryInPast => write!(f, "An API token can't expire in the past"),
+            ApiError::ApiTokenNotFound { id } => write!(f, "API token {} does not exist", id),
+            ApiError::InvalidSlug { .. } => write!(f, "A playgroup slug can only use lowercase letters, digits and dashes"),
+            ApiError::PlaygroupExists { slug } => write!(f, "Playgroup \"{}\" already exists", slug),
+            ApiError::PlaygroupNotFound { slug } => write!(f, "Playgroup \"{}\" does not exist", slug),
+            ApiError::SeasonExists { name } => write!(f, "Season \"{}\" already exists", name),
+            ApiError::SeasonNotFound { id } => write!(f, "Season {} does not exist", id),
+            ApiError::NoPointTables => write!(f, "A scoring scheme needs points for at least one pod size"),
+            ApiError::InvalidPodSize { players } => write!(f, "Pod size {} is invalid, a game must have at least two players", players),
+            ApiError::WrongPlaceCount { players, places } => write!(f, "The table for {} players has points for {} places", players, places),
+            ApiError::InvalidPoints { players } => write!(f, "The table for {} players has points that aren't a number", players),
+            ApiError::SchemeExists { name } => write!(f, "Scoring scheme \"{}\" already exists", name),
+            ApiError::SchemeNotFound { id } => write!(f, "Scoring scheme {} does not exist", id),
+            ApiError::InvalidJson { message } => write!(f, "{}", message),
+            ApiError::MissingJsonContentType => write!(f, "Expected request with `Content-Type: application/json`"),
+            ApiError::PayloadTooLarge => write!(f, "The request body is too large"),
+            ApiError::InvalidQuery { message } => write!(f, "{}", message),
+            ApiError::InvalidPath { message } => write!(f, "{}", message),
+            ApiError::DatabaseUnavailable => write!(f, "The database is unavailable, try again in a moment"),
+            ApiError::CommandersUnavailable => write!(f, "The list of commanders is not available"),
+            ApiError::Internal => write!(f, "Something went wrong on the server"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+// What every endpoint sends back when a request fails. error is the
+// message to show a person, the code and fields of details sit next to it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ErrorResponse {
     pub success: bool,
-    pub error: String
+    pub error: String,
+    #[serde(flatten)]
+    pub details: ApiError,
+}
+
+impl From<ApiError> for ErrorResponse {
+    fn from(details: ApiError) -> Self {
+        ErrorResponse {
+            success: false,
+            error: details.to_string(),
+            details
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PodSizeStats {
+    pub players: usize,
+    pub games: usize,
+    pub wins: usize,
+    pub win_rate: f64,
+}
+
+// How a commander (or partner pair) has done, commanders are sorted by name
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommanderRecord {
+    pub commanders: Vec<String>,
+    pub games: usize,
+    pub wins: usize,
+    pub win_rate: f64,
+    pub average_rank: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlayerStatsResponse {
+    pub name: String,
+    pub games: usize,
+    pub wins: usize,
+    pub win_rate: f64,
+    // None when the player hasn't played any games
+    pub average_rank: Option<f64>,
+    pub first_game: Option<DateTime<Utc>>,
+    pub last_game: Option<DateTime<Utc>>,
+    pub by_pod_size: Vec<PodSizeStats>,
+    pub most_played_commanders: Vec<CommanderRecord>,
+    pub best_commanders: Vec<CommanderRecord>,
+}
+
+// Stats for a single commander or, when there are two names, a partner pair
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommanderStats {
+    pub commanders: Vec<String>,
+    pub games: usize,
+    pub wins: usize,
+    pub win_rate: f64,
+    pub average_rank: f64,
+    // How many different players have piloted it
+    pub pilots: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OpponentRecord {
+    pub commanders: Vec<String>,
+    pub games: usize,
+    // Games where the commander we asked about placed better than this one
+    pub finished_ahead: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommanderStatsResponse {
+    pub stats: CommanderStats,
+    // Every partner pair this commander has been played in
+    pub partners: Vec<CommanderStats>,
+    pub most_played_against: Vec<OpponentRecord>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CommanderSort {
+    #[default]
+    Games,
+    Wins,
+    WinRate,
+    AverageRank,
+    Pilots,
+    Name,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+// Query parameters for GET /api/commanders/stats
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CommandersStatsQuery {
+    #[serde(default)]
+    pub sort: CommanderSort,
+    // Defaults to best first, so ascending for average_rank and name
+    pub order: Option<SortOrder>,
+    // Leave out commanders with fewer games than this
+    pub min_games: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommandersStatsResponse {
+    // Partner pairs are listed as a pair as well as under each commander
+    pub commanders: Vec<CommanderStats>,
+}
+
+// Query parameters for GET /api/head-to-head
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HeadToHeadQuery {
+    // Comma separated, at least two
+    pub players: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HeadToHeadPlayer {
+    pub name: String,
+    // Rank 1 in a shared game, whoever else was at the table
+    pub wins: usize,
+    // Finished ahead of or tied with every other player asked about
+    pub best_of_group: usize,
+    pub average_rank: f64,
+}
+
+// How player did against opponent across the shared games
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Matchup {
+    pub player: String,
+    pub opponent: String,
+    pub ahead: usize,
+    pub behind: usize,
+    pub tied: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HeadToHeadGame {
+    pub id: i32,
+    pub start_datetime: DateTime<Utc>,
+    pub winners: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HeadToHeadResponse {
+    pub games: usize,
+    pub players: Vec<HeadToHeadPlayer>,
+    pub matchups: Vec<Matchup>,
+    // Newest first
+    pub results: Vec<HeadToHeadGame>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RatingEntry {
+    pub name: String,
+    pub rating: f64,
+    // Rated games, which is every game the player was in
+    pub games: usize,
+    // How much the player's last game moved their rating
+    pub last_change: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LeaderboardResponse {
+    // Highest rating first
+    pub ratings: Vec<RatingEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RatingPoint {
+    pub game_id: i32,
+    pub start_datetime: DateTime<Utc>,
+    pub rating_before: f64,
+    pub rating_after: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RatingHistoryResponse {
+    pub name: String,
+    // Oldest game first
+    pub history: Vec<RatingPoint>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryPoint {
+    pub game_id: i32,
+    pub start_datetime: DateTime<Utc>,
+    // Pod size
+    pub players: usize,
+    pub rank: usize,
+    pub commanders: Vec<String>,
+    pub opponents: Vec<String>,
+    // Running totals including this game
+    pub win_rate: f64,
+    pub average_rank: f64,
+    // Rating after this game, None until ratings have been rebuilt
+    pub rating: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlayerHistoryResponse {
+    pub name: String,
+    // Oldest game first
+    pub history: Vec<HistoryPoint>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Season {
+    pub id: i32,
+    pub name: String,
+    // Games that started at or after start_datetime and before end_datetime
+    pub start_datetime: DateTime<Utc>,
+    pub end_datetime: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SeasonPayload {
+    pub name: String,
+    pub start_datetime: DateTime<Utc>,
+    pub end_datetime: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SeasonResponse {
+    pub season: Season,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SeasonsResponse {
+    // Newest first
+    pub seasons: Vec<Season>,
+}
+
+// Accepted by every stats and leaderboard endpoint to only count games from one season
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SeasonQuery {
+    pub season: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StandingsSort {
+    #[default]
+    Wins,
+    WinRate,
+    AverageRank,
+    Games,
+    Rating,
+}
+
+// Query parameters for GET /api/seasons/:id/leaderboard
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StandingsQuery {
+    // The other criteria break ties in the order listed in StandingsSort
+    #[serde(default)]
+    pub sort: StandingsSort,
+    // Players with fewer games are left off the leaderboard
+    pub min_games: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Standing {
+    // Players that are tied share a position
+    pub position: usize,
+    pub name: String,
+    pub games: usize,
+    pub wins: usize,
+    pub win_rate: f64,
+    pub average_rank: f64,
+    // Rating from only this season's games
+    pub rating: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SeasonLeaderboardResponse {
+    pub season: Season,
+    pub standings: Vec<Standing>,
+}
+
+// What players tied on a rank get
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TieRule {
+    // Tied players split the points for every place they cover, two players
+    // tied for 2nd in a 4 player pod each get the average of 2nd and 3rd
+    #[default]
+    Split,
+    // Tied players each get the points for the best place they share
+    Highest,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScoringScheme {
+    pub id: i32,
+    pub name: String,
+    pub tie_rule: TieRule,
+    // Points for 1st, 2nd, ... keyed by pod size. Games with a
+    // pod size that has no table don't score any points.
+    pub tables: BTreeMap<usize, Vec<f64>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScoringSchemePayload {
+    pub name: String,
+    #[serde(default)]
+    pub tie_rule: TieRule,
+    pub tables: BTreeMap<usize, Vec<f64>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScoringSchemeResponse {
+    pub scheme: ScoringScheme,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScoringSchemesResponse {
+    pub schemes: Vec<ScoringScheme>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PointsStanding {
+    // Players with the same points share a position
+    pub position: usize,
+    pub name: String,
+    pub points: f64,
+    pub games: usize,
+    pub average_points: f64,
+    // Games in a pod size the scheme has no table for
+    pub unscored_games: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PointsLeaderboardResponse {
+    pub scheme: ScoringScheme,
+    pub standings: Vec<PointsStanding>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Playgroup {
+    // Used in URLs, /api/groups/<slug>/games
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlaygroupsResponse {
+    pub groups: Vec<Playgroup>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlaygroupPayload {
+    pub slug: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlaygroupResponse {
+    pub group: Playgroup,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LoginPayload {
+    pub username: String,
+    pub password: String,
+}
+
+// The token goes in the Authorization header as a bearer token
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LoginResponse {
+    pub token: String,
+    pub username: String,
+    pub admin: bool,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct User {
+    pub username: String,
+    pub admin: bool,
+    // Revoked users can't log in and all their sessions are gone
+    pub revoked: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserPayload {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub admin: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserResponse {
+    pub user: User,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UsersResponse {
+    pub users: Vec<User>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MemberPayload {
+    pub username: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MembersResponse {
+    pub members: Vec<String>,
+}
+
+// What an API token is allowed to do. Logging in with a password gives every
+// scope the user can have, which is all of them for admins.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    #[serde(rename = "games:write")]
+    GamesWrite,
+    #[serde(rename = "players:write")]
+    PlayersWrite,
+    // Seasons, scoring schemes, members, users and playgroups
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::GamesWrite, Scope::PlayersWrite, Scope::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::GamesWrite => "games:write",
+            Scope::PlayersWrite => "players:write",
+            Scope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Scope> {
+        Scope::ALL.into_iter().find(|candidate| candidate.as_str() == scope)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiToken {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    // None means the token never expires
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiTokenPayload {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// The secret is only ever sent back when the token is created, the server just keeps a hash of it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiTokenCreatedResponse {
+    pub token: ApiToken,
+    pub secret: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiTokensResponse {
+    pub tokens: Vec<ApiToken>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEntity {
+    Player,
+    Game,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditEntry {
+    pub id: i32,
+    // None when the user has since been removed
+    pub actor: Option<String>,
+    pub entity: AuditEntity,
+    pub entity_id: i32,
+    pub action: AuditAction,
+    pub timestamp: DateTime<Utc>,
+    // None for a create
+    pub before: Option<serde_json::Value>,
+    // None for a delete
+    pub after: Option<serde_json::Value>,
+}
+
+// Query parameters for GET /api/audit, every one of them is optional
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AuditQuery {
+    pub entity: Option<AuditEntity>,
+    pub entity_id: Option<i32>,
+    pub action: Option<AuditAction>,
+    // Username of whoever made the change
+    pub actor: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    // Only entries that come after this entry ID in newest first order
+    pub before: Option<i32>,
+    // GET /api/audit never hands out more than 100 entries at once
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditResponse {
+    pub entries: Vec<AuditEntry>,
+    #[serde(default)]
+    pub next_cursor: Option<i32>,
 }
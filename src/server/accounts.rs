@@ -0,0 +1,254 @@
+use axum::Extension;
+use argon2::{
+    Argon2,
+    PasswordHash,
+    PasswordHasher,
+    PasswordVerifier,
+    password_hash::{self, SaltString, rand_core::OsRng},
+};
+use chrono::{DateTime, Duration, Utc};
+use rand::{Rng, distributions::Alphanumeric};
+use sha2::{Digest, Sha256};
+use std::{env, error::Error};
+use ormos::messages::*;
+use crate::{BearerAuthWithJsonResponse, NamePath, error::{ServerError, ServerResult}, extract::{Json, Path}, playgroups::CurrentPlaygroup, store::{SharedStore, Store, StoreError}};
+
+// How long a login lasts before the user has to log in again
+const SESSION_DAYS: i64 = 30;
+
+// A password nobody knows, hashed with the default Argon2 parameters.
+// Logging in as someone who doesn't exist is checked against this so it
+// takes as long as getting a real user's password wrong.
+const DUMMY_HASH: &str = "$argon2id$v=19$m=19456,t=2,p=1$qnXE404pEzyJmZ6hna3ZfQ$WcyobX7rMJsqwfDbb8o/Txy+1XdKczTUdjpsQk2i8dw";
+
+// The logged in user making a request, put into the request by bearer_auth
+#[derive(Clone, Debug)]
+pub struct CurrentUser {
+    pub id: i32,
+    pub username: String,
+    pub admin: bool,
+    // allowed_scopes for a password login, whatever the token was given for an API token
+    pub scopes: Vec<Scope>,
+    // Whether the request came with an API token rather than a login
+    pub api_token: bool,
+}
+
+impl CurrentUser {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+// The first admin is created on startup when there are no users yet.
+// Falls back to POST_TOKEN so existing deployments keep their secret.
+fn get_admin_password() -> Option<String> {
+    env::var("ADMIN_PASSWORD")
+        .or(env::var("POST_TOKEN"))
+        .ok()
+}
+
+// Session tokens are long and random so a plain SHA-256 is enough,
+// only passwords need a slow hash
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub fn generate_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect()
+}
+
+fn hash_password(password: &str) -> Result<String, password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(_) => false
+    }
+}
+
+// Everything a user can do, which is what logging in with a password gives
+// and the most an API token of theirs can be given. Only admins get Scope::Admin.
+pub fn allowed_scopes(admin: bool) -> Vec<Scope> {
+    Scope::ALL.into_iter().filter(|scope| admin || *scope != Scope::Admin).collect()
+}
+
+pub fn require_admin(user: &CurrentUser) -> ServerResult<()> {
+    if user.admin {
+        Ok(())
+    }
+    else {
+        Err(ApiError::AdminOnly.into())
+    }
+}
+
+pub async fn setup_admin(store: &dyn Store) -> Result<(), Box<dyn Error>> {
+    if store.user_count().await? == 0 {
+        // Without either variable there's no password anyone could guess,
+        // it's only ever shown here
+        let (password, generated) = match get_admin_password() {
+            Some(password) => (password, false),
+            None => (generate_token(), true)
+        };
+
+        let password_hash = hash_password(&password)
+            .map_err(|error| format!("Failed to hash the admin password: {}", error))?;
+        store.create_user("admin", &password_hash, true).await?;
+
+        if generated {
+            println!("Created user \"admin\" with password {}", password);
+        }
+        else {
+            println!("Created user \"admin\"");
+        }
+    }
+
+    Ok(())
+}
+
+// The user a bearer token belongs to, None if the token is unknown,
+// expired or belongs to a revoked user. The token can either be
+// from logging in or an API token, which acts as the user who created
+// it limited to its scopes.
+pub async fn authenticate(store: &dyn Store, token: &str) -> Result<Option<CurrentUser>, StoreError> {
+    let token_hash = hash_token(token);
+
+    match store.session_user(&token_hash).await? {
+        Some(user) => Ok(Some(user)),
+        None => store.api_token_user(&token_hash).await
+    }
+}
+
+// Admins can write to every playgroup, everyone else only to the ones they're a member of
+pub async fn can_write(store: &dyn Store, user: &CurrentUser, playgroup_id: i32) -> Result<bool, StoreError> {
+    if user.admin {
+        return Ok(true);
+    }
+
+    store.is_member(playgroup_id, user.id).await
+}
+
+pub async fn find_user(store: &dyn Store, username: &str) -> ServerResult<i32> {
+    match store.user_id(username).await? {
+        Some(user_id) => Ok(user_id),
+        None => Err(ApiError::UserNotFound { username: username.to_string() }.into())
+    }
+}
+
+pub async fn post_login(Extension(store): Extension<SharedStore>, Json(payload): Json<LoginPayload>) -> ServerResult<Json<LoginResponse>> {
+    let row = store.login_user(&payload.username).await?;
+
+    // Same error and the same amount of hashing either way so
+    // logins can't be used to find out who has an account
+    let password_hash = row.as_ref().map_or(DUMMY_HASH, |(_, _, password_hash, _)| password_hash.as_str());
+    let verified = verify_password(&payload.password, password_hash);
+
+    let Some((user_id, username, _, admin)) = row.filter(|_| verified) else {
+        return Err(ApiError::IncorrectLogin.into());
+    };
+
+    let token = generate_token();
+    let expires_at: DateTime<Utc> = Utc::now() + Duration::days(SESSION_DAYS);
+
+    store.create_session(user_id, &hash_token(&token), expires_at).await?;
+
+    Ok(Json(LoginResponse {
+        token,
+        username,
+        admin,
+        expires_at
+    }))
+}
+
+pub async fn post_logout(Extension(store): Extension<SharedStore>, bearer: BearerAuthWithJsonResponse) -> ServerResult<Json<PostResponse>> {
+    store.delete_session(&hash_token(bearer.0.token())).await?;
+
+    Ok(Json(PostResponse { success: true, error: None }))
+}
+
+pub async fn get_users(Extension(store): Extension<SharedStore>, Extension(user): Extension<CurrentUser>) -> ServerResult<Json<UsersResponse>> {
+    require_admin(&user)?;
+
+    Ok(Json(UsersResponse {
+        users: store.users().await?
+    }))
+}
+
+pub async fn post_user(Extension(store): Extension<SharedStore>, Extension(user): Extension<CurrentUser>, Json(payload): Json<UserPayload>) -> ServerResult<Json<UserResponse>> {
+    require_admin(&user)?;
+
+    if payload.username.trim().is_empty() {
+        return Err(ApiError::MissingField { field: String::from("username") }.into());
+    }
+
+    if payload.password.is_empty() {
+        return Err(ApiError::MissingField { field: String::from("password") }.into());
+    }
+
+    let password_hash = hash_password(&payload.password).map_err(ServerError::internal)?;
+
+    match store.create_user(&payload.username, &password_hash, payload.admin).await {
+        Ok(_) => Ok(Json(UserResponse {
+            user: User {
+                username: payload.username,
+                admin: payload.admin,
+                revoked: false
+            }
+        })),
+        Err(StoreError::Duplicate) => Err(ApiError::UserExists { username: payload.username }.into()),
+        Err(error) => Err(ServerError::from(error))
+    }
+}
+
+// Locks one user out without touching anyone else. Their sessions are thrown
+// away and their API tokens stop working, the games they entered stay.
+pub async fn post_revoke_user(Extension(store): Extension<SharedStore>, Extension(user): Extension<CurrentUser>, Path(NamePath { name }): Path<NamePath>) -> ServerResult<Json<PostResponse>> {
+    require_admin(&user)?;
+
+    if name == user.username {
+        return Err(ApiError::CannotRevokeSelf.into());
+    }
+
+    let user_id = find_user(&*store, &name).await?;
+
+    store.revoke_user(user_id).await?;
+
+    Ok(Json(PostResponse { success: true, error: None }))
+}
+
+pub async fn get_members(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>) -> ServerResult<Json<MembersResponse>> {
+    Ok(Json(MembersResponse {
+        members: store.members(group.id).await?
+    }))
+}
+
+pub async fn post_member(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Json(payload): Json<MemberPayload>) -> ServerResult<Json<PostResponse>> {
+    require_admin(&user)?;
+
+    let user_id = find_user(&*store, &payload.username).await?;
+
+    store.add_member(group.id, user_id).await?;
+
+    Ok(Json(PostResponse { success: true, error: None }))
+}
+
+// Takes away one member's access to this playgroup, they can still log in
+pub async fn delete_member(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Path(NamePath { name }): Path<NamePath>) -> ServerResult<Json<PostResponse>> {
+    require_admin(&user)?;
+
+    let user_id = find_user(&*store, &name).await?;
+
+    if !store.remove_member(group.id, user_id).await? {
+        return Err(ApiError::MemberNotFound { username: name, playgroup: group.slug }.into());
+    }
+
+    Ok(Json(PostResponse { success: true, error: None }))
+}
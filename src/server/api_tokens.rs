@@ -0,0 +1,89 @@
+use axum::Extension;
+use chrono::Utc;
+use ormos::messages::*;
+use crate::{IdPath, NamePath, accounts::{self, CurrentUser, allowed_scopes, generate_token, hash_token}, error::ServerResult, extract::{Json, Path}, store::SharedStore};
+
+// Anything we don't recognise is dropped rather than granted
+pub fn parse_scopes(scopes: Vec<String>) -> Vec<Scope> {
+    scopes.iter().filter_map(|scope| Scope::parse(scope)).collect()
+}
+
+// Otherwise a token could make another one that outlives it, or still
+// works after it's revoked, so an expiry or revocation would mean nothing
+fn require_login(user: &CurrentUser) -> ServerResult<()> {
+    if user.api_token {
+        Err(ApiError::LoginRequired.into())
+    }
+    else {
+        Ok(())
+    }
+}
+
+fn validate_api_token(user: &CurrentUser, payload: &ApiTokenPayload) -> ServerResult<()> {
+    if payload.name.trim().is_empty() {
+        return Err(ApiError::MissingField { field: String::from("name") }.into());
+    }
+
+    if payload.scopes.is_empty() {
+        return Err(ApiError::MissingField { field: String::from("scopes") }.into());
+    }
+
+    // A token can never do more than whoever made it, or than they could do at all
+    let allowed = allowed_scopes(user.admin);
+    for scope in payload.scopes.iter() {
+        if !user.has_scope(*scope) || !allowed.contains(scope) {
+            return Err(ApiError::ScopeNotAllowed { scope: *scope }.into());
+        }
+    }
+
+    if payload.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(ApiError::ExpiryInPast.into());
+    }
+
+    Ok(())
+}
+
+pub async fn get_api_tokens(Extension(store): Extension<SharedStore>, Extension(user): Extension<CurrentUser>) -> ServerResult<Json<ApiTokensResponse>> {
+    require_login(&user)?;
+
+    Ok(Json(ApiTokensResponse {
+        tokens: store.api_tokens(user.id).await?
+    }))
+}
+
+pub async fn post_api_token(Extension(store): Extension<SharedStore>, Extension(user): Extension<CurrentUser>, Json(payload): Json<ApiTokenPayload>) -> ServerResult<Json<ApiTokenCreatedResponse>> {
+    require_login(&user)?;
+    validate_api_token(&user, &payload)?;
+
+    let secret = generate_token();
+    let mut scopes = payload.scopes.clone();
+    scopes.sort_by_key(|scope| scope.as_str());
+    scopes.dedup();
+
+    let token = store.create_api_token(user.id, &payload.name, &hash_token(&secret), &scopes, payload.expires_at).await?;
+
+    Ok(Json(ApiTokenCreatedResponse {
+        token,
+        secret
+    }))
+}
+
+// So admins can see the tokens they can revoke
+pub async fn get_user_api_tokens(Extension(store): Extension<SharedStore>, Path(NamePath { name }): Path<NamePath>) -> ServerResult<Json<ApiTokensResponse>> {
+    let user_id = accounts::find_user(&*store, &name).await?;
+
+    Ok(Json(ApiTokensResponse {
+        tokens: store.api_tokens(user_id).await?
+    }))
+}
+
+// Users can revoke their own tokens, admins can revoke anyone's
+pub async fn delete_api_token(Extension(store): Extension<SharedStore>, Extension(user): Extension<CurrentUser>, Path(IdPath { id }): Path<IdPath>) -> ServerResult<Json<PostResponse>> {
+    require_login(&user)?;
+
+    if !store.revoke_api_token(id, user.id, user.has_scope(Scope::Admin)).await? {
+        return Err(ApiError::ApiTokenNotFound { id }.into());
+    }
+
+    Ok(Json(PostResponse { success: true, error: None }))
+}
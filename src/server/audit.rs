@@ -0,0 +1,72 @@
+use axum::Extension;
+use serde::Serialize;
+use ormos::messages::*;
+use crate::{accounts::{CurrentUser, require_admin}, error::ServerResult, extract::{Json, Query}, playgroups::CurrentPlaygroup, store::{SharedStore, StoreError}};
+
+// Most entries GET /api/audit hands out at once, also what you get without a limit
+const MAX_AUDIT_PAGE: usize = 100;
+
+pub fn entity_str(entity: AuditEntity) -> &'static str {
+    match entity {
+        AuditEntity::Player => "player",
+        AuditEntity::Game => "game",
+    }
+}
+
+pub fn action_str(action: AuditAction) -> &'static str {
+    match action {
+        AuditAction::Create => "create",
+        AuditAction::Update => "update",
+        AuditAction::Delete => "delete",
+        AuditAction::Restore => "restore",
+    }
+}
+
+// Only ever written by the stores, so anything else means the table was edited by hand
+pub fn parse_entity(entity: &str) -> Result<AuditEntity, StoreError> {
+    match entity {
+        "player" => Ok(AuditEntity::Player),
+        "game" => Ok(AuditEntity::Game),
+        _ => Err(StoreError::Inconsistent(format!("Unknown audit entity {}", entity)))
+    }
+}
+
+pub fn parse_action(action: &str) -> Result<AuditAction, StoreError> {
+    match action {
+        "create" => Ok(AuditAction::Create),
+        "update" => Ok(AuditAction::Update),
+        "delete" => Ok(AuditAction::Delete),
+        "restore" => Ok(AuditAction::Restore),
+        _ => Err(StoreError::Inconsistent(format!("Unknown audit action {}", action)))
+    }
+}
+
+// None when there's nothing to snapshot, i.e. the entity doesn't exist
+pub fn snapshot<T: Serialize>(value: &Option<T>) -> Option<serde_json::Value> {
+    value.as_ref().and_then(|value| serde_json::to_value(value).ok())
+}
+
+// What an entity looked like before and after a change
+pub struct Change {
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+pub async fn get_audit(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Query(query): Query<AuditQuery>) -> ServerResult<Json<AuditResponse>> {
+    require_admin(&user)?;
+
+    let limit = query.limit.unwrap_or(MAX_AUDIT_PAGE).min(MAX_AUDIT_PAGE);
+
+    let entries = store.audit_entries(group.id, &query, limit).await?;
+
+    let next_cursor = if limit > 0 && entries.len() == limit {
+        entries.last().map(|entry| entry.id)
+    } else {
+        None
+    };
+
+    Ok(Json(AuditResponse {
+        entries,
+        next_cursor
+    }))
+}
@@ -0,0 +1,445 @@
+use axum::Extension;
+use itertools::Itertools;
+use reqwest::Method;
+use serde::{Serialize, Deserialize};
+use std::{collections::HashMap, error::Error, fs, fs::File, io::Write, sync::{Arc, PoisonError, RwLock}, thread, time::Duration};
+use ormos::messages::*;
+use crate::{error::{ServerError, ServerResult}, extract::Json};
+
+// Written by generate_commanders in the directory the server runs in
+const COMMANDERS_FILE: &str = "commanders.json";
+
+// How many close matches an unknown commander gets at most
+const MAX_SUGGESTIONS: usize = 5;
+// Jaro-Winkler similarity a commander needs to count as a close match
+const MIN_SIMILARITY: f64 = 0.85;
+// Anything shorter is in too many names to say which one was meant
+const MIN_SUBSTRING_LENGTH: usize = 3;
+
+#[derive(Deserialize)]
+struct ScryfallLegalities {
+    commander: String
+}
+
+#[derive(Deserialize)]
+struct ScryfallPart {
+    component: String,
+    name: String
+}
+
+#[derive(Deserialize)]
+struct ScryfallCardFace {
+    type_line: Option<String>,
+    name: String,
+    oracle_text: Option<String>
+}
+
+#[derive(Deserialize)]
+struct ScryfallCard {
+    type_line: Option<String>,
+    name: String,
+    legalities: ScryfallLegalities,
+    games: Vec<String>,
+    oracle_text: Option<String>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    all_parts: Option<Vec<ScryfallPart>>,
+    card_faces: Option<Vec<ScryfallCardFace>>
+}
+
+// What lets a commander share the command zone with a second one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Pairing {
+    // Any other commander with Partner
+    Partner,
+    // Only commanders with the same kind of Partner, e.g. Partner—Survivors
+    PartnerVariant { variant: String },
+    // Only the one card named, which has to name this one back
+    PartnerWith { name: String },
+    // Any other commander with Friends forever
+    FriendsForever,
+    // A Background
+    ChooseABackground,
+    // The Backgrounds themselves, they go with Choose a Background
+    Background,
+    // A Time Lord Doctor
+    DoctorsCompanion,
+    // Time Lord Doctors, they go with Doctor's companion
+    Doctor,
+}
+
+impl Pairing {
+    fn allows(&self, other: &Pairing, own_name: &str, other_name: &str) -> bool {
+        match (self, other) {
+            (Pairing::Partner, Pairing::Partner)
+                | (Pairing::FriendsForever, Pairing::FriendsForever)
+                | (Pairing::ChooseABackground, Pairing::Background)
+                | (Pairing::Background, Pairing::ChooseABackground)
+                | (Pairing::DoctorsCompanion, Pairing::Doctor)
+                | (Pairing::Doctor, Pairing::DoctorsCompanion) => true,
+            (Pairing::PartnerVariant { variant }, Pairing::PartnerVariant { variant: other_variant }) => variant == other_variant,
+            (Pairing::PartnerWith { name }, Pairing::PartnerWith { name: other_partner }) => name == other_name && other_partner == own_name,
+            _ => false
+        }
+    }
+
+    // What the other commander would need, for the error message
+    fn requirement(&self, own_name: &str) -> String {
+        match self {
+            Pairing::Partner => format!("\"{}\" can only be paired with another commander with Partner", own_name),
+            Pairing::PartnerVariant { variant } => format!("\"{}\" can only be paired with another commander with Partner—{}", own_name, variant),
+            Pairing::PartnerWith { name } => format!("\"{}\" can only be paired with \"{}\"", own_name, name),
+            Pairing::FriendsForever => format!("\"{}\" can only be paired with another commander with Friends forever", own_name),
+            Pairing::ChooseABackground => format!("\"{}\" can only be paired with a Background", own_name),
+            Pairing::Background => format!("\"{}\" can only be paired with a commander with Choose a Background", own_name),
+            Pairing::DoctorsCompanion => format!("\"{}\" can only be paired with a Time Lord Doctor", own_name),
+            Pairing::Doctor => format!("\"{}\" can only be paired with a commander with Doctor's companion", own_name),
+        }
+    }
+}
+
+fn find_pairing(keywords: &[String], type_line: &str, oracle_text: &str) -> Option<Pairing> {
+    let has_keyword = |keyword: &str| keywords.iter().any(|card_keyword| card_keyword == keyword);
+    // The ability is followed by reminder text, e.g.
+    // "Partner—Survivors (You can have two commanders if both have this ability.)"
+    let partner_variant = oracle_text
+        .lines()
+        .find_map(|line| line.strip_prefix("Partner—"))
+        .map(|variant| variant.split(" (").next().unwrap_or(variant).trim());
+
+    if let Some(variant) = partner_variant {
+        Some(Pairing::PartnerVariant { variant: variant.to_string() })
+    }
+    else if has_keyword("Partner with") {
+        // The name is followed by reminder text, e.g.
+        // "Partner with Pir, Imaginative Rascal (When this creature enters, ..."
+        let name = oracle_text.lines().find_map(|line| line.strip_prefix("Partner with "))?;
+        let name = name.split(" (").next().unwrap_or(name).trim();
+        Some(Pairing::PartnerWith { name: name.to_string() })
+    }
+    else if has_keyword("Partner") {
+        Some(Pairing::Partner)
+    }
+    else if has_keyword("Friends forever") {
+        Some(Pairing::FriendsForever)
+    }
+    else if has_keyword("Choose a Background") {
+        Some(Pairing::ChooseABackground)
+    }
+    else if has_keyword("Doctor's companion") {
+        Some(Pairing::DoctorsCompanion)
+    }
+    else if type_line.contains("Background") {
+        Some(Pairing::Background)
+    }
+    // Doctor's companion only allows Doctors without any other creature types
+    else if type_line.ends_with("— Time Lord Doctor") {
+        Some(Pairing::Doctor)
+    }
+    else {
+        None
+    }
+}
+
+// One entry of COMMANDERS_FILE
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CommanderCard {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pairing: Option<Pairing>,
+}
+
+fn generate_commanders() -> Result<Vec<CommanderCard>, Box<dyn Error>> {
+    println!("Getting bulk data URI");
+    let bulk_data_response: BulkDataResponse = reqwest::blocking::get("https://api.scryfall.com/bulk-data/default-cards")?
+        .json()?;
+
+    println!("Getting bulk data from {}", bulk_data_response.download_uri);
+    let cards: Vec<ScryfallCard> = reqwest::blocking::Client::new()
+        .request(Method::GET, bulk_data_response.download_uri)
+        .timeout(Duration::from_secs(60 * 20))
+        .send()?
+        .json()?;
+
+    let mut commanders: Vec<CommanderCard> = Vec::new();
+
+    for card in cards {
+        let mut type_line = card.type_line;
+        let mut name = card.name;
+        let mut oracle_text = card.oracle_text;
+
+        if card.legalities.commander != "legal" {
+            continue
+        }
+
+        if !card.games.contains(&String::from("paper")) {
+            continue
+        }
+
+        // Skip stuff like Brisela
+        if let Some(parts) = card.all_parts {
+            let meld_result_parts: Vec<&ScryfallPart> = parts.iter().filter(|part| part.component == "meld_result").collect();
+            if !meld_result_parts.is_empty() && meld_result_parts[0].name == *name {
+                continue
+            }
+        }
+
+        if let Some(faces) = card.card_faces {
+            if let Some(inner_type_line) = &faces[0].type_line {
+                type_line = Some(inner_type_line.clone());
+            }
+            if let Some(inner_oracle_text) = &faces[0].oracle_text {
+                oracle_text = Some(inner_oracle_text.clone());
+            }
+            name = faces[0].name.clone();
+        }
+
+
+        if let Some(type_line) = type_line {
+            if commanders.iter().any(|commander| commander.name == name) {
+                continue
+            }
+            let oracle_text = oracle_text.unwrap_or_default();
+            if (type_line.contains("Creature") && type_line.contains("Legendary"))
+                || type_line.contains("Background")
+                || name == "Grist, the Hunger Tide"
+                || oracle_text.contains("can be your commander") {
+                commanders.push(CommanderCard {
+                    pairing: find_pairing(&card.keywords, &type_line, &oracle_text),
+                    name,
+                });
+            }
+        }
+    }
+
+    commanders.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut file = File::create(COMMANDERS_FILE)?;
+    let s = serde_json::to_string(&commanders)?;
+    write!(file, "{}", s)?;
+
+    println!("Loaded {} commanders", commanders.len());
+
+    Ok(commanders)
+}
+
+// What COMMANDERS_FILE can hold. Older servers only wrote the names.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CommandersFile {
+    Cards(Vec<CommanderCard>),
+    Names(Vec<String>),
+}
+
+// A list from generate_commanders set up for looking commanders up by name
+struct CommanderList {
+    names: Vec<String>,
+    pairings: HashMap<String, Option<Pairing>>,
+    // False for a list of bare names, which says nothing about pairing
+    has_pairings: bool,
+}
+
+impl From<Vec<CommanderCard>> for CommanderList {
+    fn from(commanders: Vec<CommanderCard>) -> Self {
+        CommanderList {
+            names: commanders.iter().map(|commander| commander.name.clone()).collect(),
+            pairings: commanders.into_iter().map(|commander| (commander.name, commander.pairing)).collect(),
+            has_pairings: true,
+        }
+    }
+}
+
+impl CommanderList {
+    fn without_pairings(names: Vec<String>) -> Self {
+        CommanderList {
+            pairings: names.iter().map(|name| (name.clone(), None)).collect(),
+            names,
+            has_pairings: false,
+        }
+    }
+}
+
+// The commander list the handlers check games against. It's shared with
+// the thread that downloads a fresh one every day and is None until
+// there's a list, either left behind by the last run or downloaded.
+#[derive(Clone, Default)]
+pub struct Commanders(Arc<RwLock<Option<Arc<CommanderList>>>>);
+
+impl Commanders {
+    pub fn new(commanders: Vec<CommanderCard>) -> Self {
+        Commanders::from_list(commanders.into())
+    }
+
+    fn from_list(list: CommanderList) -> Self {
+        Commanders(Arc::new(RwLock::new(Some(Arc::new(list)))))
+    }
+
+    pub fn from_file() -> Self {
+        let Ok(json_text) = fs::read_to_string(COMMANDERS_FILE) else {
+            return Commanders::default();
+        };
+
+        match serde_json::from_str::<CommandersFile>(&json_text) {
+            Ok(CommandersFile::Cards(commanders)) => Commanders::new(commanders),
+            Ok(CommandersFile::Names(names)) => {
+                eprintln!("{} has no pairings, checking names only until it's downloaded again", COMMANDERS_FILE);
+                Commanders::from_list(CommanderList::without_pairings(names))
+            }
+            Err(error) => {
+                eprintln!("Ignoring {}: {}", COMMANDERS_FILE, error);
+                Commanders::default()
+            }
+        }
+    }
+
+    // Downloads right away when there's no list yet, or only one without
+    // pairings, so only a fresh or upgraded server pays for it on startup,
+    // and then once a day after that
+    pub fn spawn_refresh(&self) -> thread::JoinHandle<()> {
+        let commanders = self.clone();
+        let missing = self.list().map_or(true, |list| !list.has_pairings);
+
+        thread::spawn(move || {
+            if missing {
+                commanders.refresh();
+            }
+            loop {
+                thread::sleep(Duration::from_secs(60 * 60 * 24));
+                commanders.refresh();
+            }
+        })
+    }
+
+    // A failed download keeps the list we had and tries again the next day
+    fn refresh(&self) {
+        match generate_commanders() {
+            Ok(commanders) => *self.0.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(commanders.into())),
+            Err(error) => eprintln!("Failed to generate commanders: {}", error)
+        }
+    }
+
+    fn list(&self) -> ServerResult<Arc<CommanderList>> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+            .ok_or(ServerError(ApiError::CommandersUnavailable))
+    }
+
+    // Commanders have to be spelled the way Scryfall does, otherwise the
+    // same card shows up under several names in the stats. Cards newer
+    // than the list can still be entered with allow_unknown_commanders.
+    // Until the first download finishes there's nothing to check against,
+    // and that shouldn't stop anyone from recording games.
+    pub fn check(&self, players: &[Player], query: &GameWriteQuery) -> ServerResult<()> {
+        let Ok(known) = self.list() else {
+            eprintln!("No commander list yet, not checking commanders");
+            return Ok(());
+        };
+
+        for player in players {
+            if !query.allow_unknown_commanders {
+                if let Some(commander) = player.commanders.iter().find(|commander| !known.pairings.contains_key(*commander)) {
+                    return Err(ApiError::UnknownCommander {
+                        player: player.name.clone(),
+                        commander: commander.clone(),
+                        suggestions: close_matches(&known.names, commander)
+                    }.into());
+                }
+            }
+
+            if known.has_pairings {
+                check_pair(&known, player)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// A second commander is only allowed if the pairing abilities of the two
+// go together. Commanders that aren't in the list were let through on
+// purpose and there's nothing to check them against.
+fn check_pair(known: &CommanderList, player: &Player) -> ServerResult<()> {
+    let [first, second] = player.commanders.as_slice() else {
+        return Ok(());
+    };
+
+    let (Some(first_pairing), Some(second_pairing)) = (known.pairings.get(first), known.pairings.get(second)) else {
+        return Ok(());
+    };
+
+    let reason = match (first_pairing, second_pairing) {
+        _ if first == second => format!("\"{}\" can't be paired with itself", first),
+        (Some(pairing), Some(other)) if pairing.allows(other, first, second) => return Ok(()),
+        (Some(pairing), _) => pairing.requirement(first),
+        (None, Some(pairing)) => pairing.requirement(second),
+        (None, None) => String::from("neither of them can have a second commander")
+    };
+
+    Err(ApiError::IllegalPair {
+        player: player.name.clone(),
+        commanders: vec![first.clone(), second.clone()],
+        reason
+    }.into())
+}
+
+// Commanders that look like name, best match first. Anything that contains
+// name counts as a close match so "Atraxa" finds "Atraxa, Praetors' Voice",
+// the more of the commander's name it covers the better.
+fn close_matches(known: &[String], name: &str) -> Vec<String> {
+    let name = name.to_lowercase();
+    let name_length = name.chars().count();
+
+    known
+        .iter()
+        .map(|commander| {
+            let commander_lowercase = commander.to_lowercase();
+            let mut similarity = strsim::jaro_winkler(&name, &commander_lowercase);
+            if name_length >= MIN_SUBSTRING_LENGTH && commander_lowercase.contains(&name) {
+                let covered = name_length as f64 / commander_lowercase.chars().count() as f64;
+                similarity = similarity.max(MIN_SIMILARITY + (1.0 - MIN_SIMILARITY) * covered);
+            }
+            (similarity, commander)
+        })
+        .filter(|(similarity, _)| *similarity >= MIN_SIMILARITY)
+        .sorted_by(|(a, _), (b, _)| b.total_cmp(a))
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, commander)| commander.clone())
+        .collect()
+}
+
+pub async fn get_commanders(Extension(commanders): Extension<Commanders>) -> ServerResult<Json<CommandersResponse>> {
+    Ok(Json(CommandersResponse {
+        commanders: commanders.list()?.names.clone()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known() -> Vec<String> {
+        ["Atraxa, Grand Unifier", "Atraxa, Praetors' Voice", "Krenko", "Krenko, Mob Boss", "Krenko, Tin Street Kingpin", "Tymna the Weaver"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    #[test]
+    fn longer_substrings_rank_higher() {
+        assert_eq!(close_matches(&known(), "krenko"), ["Krenko", "Krenko, Mob Boss", "Krenko, Tin Street Kingpin"]);
+        assert_eq!(close_matches(&known(), "Atraxa"), ["Atraxa, Grand Unifier", "Atraxa, Praetors' Voice"]);
+    }
+
+    #[test]
+    fn short_substrings_are_not_matches() {
+        assert!(close_matches(&known(), "a").is_empty());
+        assert!(close_matches(&known(), "ti").is_empty());
+    }
+
+    #[test]
+    fn typos_still_match() {
+        assert_eq!(close_matches(&known(), "Tymna the Weavr"), ["Tymna the Weaver"]);
+    }
+}
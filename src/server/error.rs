@@ -0,0 +1,127 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use std::fmt;
+use ormos::{messages::*, validation::GameError};
+use crate::store::StoreError;
+
+// What handlers return when a request fails. It's sent as an ErrorResponse
+// with the status that goes with the error, see status.
+#[derive(Debug)]
+pub struct ServerError(pub ApiError);
+
+pub type ServerResult<T> = Result<T, ServerError>;
+
+impl ServerError {
+    // For failures the client can't do anything about. The cause only
+    // goes to the log, the client gets the error's generic message.
+    pub fn caused_by(error: ApiError, cause: impl fmt::Display) -> Self {
+        eprintln!("{}: {}", error, cause);
+        ServerError(error)
+    }
+
+    pub fn internal(cause: impl fmt::Display) -> Self {
+        ServerError::caused_by(ApiError::Internal, cause)
+    }
+}
+
+impl From<ApiError> for ServerError {
+    fn from(error: ApiError) -> Self {
+        ServerError(error)
+    }
+}
+
+impl From<GameError> for ServerError {
+    fn from(error: GameError) -> Self {
+        ServerError(error.into())
+    }
+}
+
+impl From<StoreError> for ServerError {
+    fn from(error: StoreError) -> Self {
+        match error {
+            StoreError::UnknownPlayer(player) => ServerError(ApiError::UnknownPlayer { player }),
+            StoreError::Invalid(error) => error.into(),
+            // Losing the connection or running out of them usually fixes itself
+            StoreError::Database(ref cause @ (sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed)) => {
+                ServerError::caused_by(ApiError::DatabaseUnavailable, cause)
+            },
+            _ => ServerError::internal(error)
+        }
+    }
+}
+
+fn status(error: &ApiError) -> StatusCode {
+    match error {
+        ApiError::TooFewPlayers
+            | ApiError::DuplicatePlayer { .. }
+            | ApiError::InvalidDateRange
+            | ApiError::InvalidRank { .. }
+            | ApiError::NoFirstPlace
+            | ApiError::InvalidRankSequence { .. }
+            | ApiError::NoCommanders { .. }
+            | ApiError::EmptyCommander { .. }
+            | ApiError::UnknownPlayer { .. }
+            | ApiError::UnknownCommander { .. }
+            | ApiError::IllegalPair { .. }
+            | ApiError::TooManyCommanders { .. }
+            | ApiError::UnknownCursor { .. }
+            | ApiError::TooFewHeadToHeadPlayers
+            | ApiError::MissingField { .. }
+            | ApiError::CannotRevokeSelf
+            | ApiError::ExpiryInPast
+            | ApiError::InvalidSlug { .. }
+            | ApiError::NoPointTables
+            | ApiError::InvalidPodSize { .. }
+            | ApiError::WrongPlaceCount { .. }
+            | ApiError::InvalidPoints { .. }
+            | ApiError::InvalidJson { .. }
+            | ApiError::InvalidQuery { .. }
+            | ApiError::InvalidPath { .. } => StatusCode::BAD_REQUEST,
+
+        ApiError::MissingJsonContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+
+        ApiError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+
+        ApiError::MissingToken
+            | ApiError::MalformedToken
+            | ApiError::InvalidToken
+            | ApiError::IncorrectLogin => StatusCode::UNAUTHORIZED,
+
+        ApiError::MissingScope { .. }
+            | ApiError::NotAMember { .. }
+            | ApiError::AdminOnly
+            | ApiError::LoginRequired
+            | ApiError::ScopeNotAllowed { .. } => StatusCode::FORBIDDEN,
+
+        ApiError::GameNotFound { .. }
+            | ApiError::GameNotDeleted { .. }
+            | ApiError::PlayerNotFound { .. }
+            | ApiError::CommanderNotFound { .. }
+            | ApiError::UserNotFound { .. }
+            | ApiError::MemberNotFound { .. }
+            | ApiError::ApiTokenNotFound { .. }
+            | ApiError::PlaygroupNotFound { .. }
+            | ApiError::SeasonNotFound { .. }
+            | ApiError::SchemeNotFound { .. } => StatusCode::NOT_FOUND,
+
+        ApiError::PlayerExists { .. }
+            | ApiError::UserExists { .. }
+            | ApiError::PlaygroupExists { .. }
+            | ApiError::SeasonExists { .. }
+            | ApiError::SchemeExists { .. } => StatusCode::CONFLICT,
+
+        ApiError::DatabaseUnavailable
+            | ApiError::CommandersUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+
+        ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        (status(&self.0), Json(ErrorResponse::from(self.0))).into_response()
+    }
+}
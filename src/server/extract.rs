@@ -0,0 +1,79 @@
+use axum::{
+    extract::{FromRequest, FromRequestParts, Request, rejection::{JsonRejection, PathRejection, QueryRejection}},
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Response},
+    async_trait
+};
+use serde::{Serialize, de::DeserializeOwned};
+use ormos::messages::ApiError;
+use crate::error::ServerError;
+
+// Drop-in replacements for axum's Json, Query and Path. axum rejects
+// input it can't deserialize with a plaintext body, these send the
+// same ErrorResponse as every other error instead.
+
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ServerError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::Json::<T>::from_request(request, state).await {
+            Ok(axum::Json(value)) => Ok(Json(value)),
+            Err(JsonRejection::MissingJsonContentType(_)) => Err(ServerError(ApiError::MissingJsonContentType)),
+            // The body went over DefaultBodyLimit
+            Err(JsonRejection::BytesRejection(rejection)) if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE => Err(ServerError(ApiError::PayloadTooLarge)),
+            Err(rejection) => Err(ServerError(ApiError::InvalidJson { message: rejection.body_text() }))
+        }
+    }
+}
+
+// Responses go out the same way as with axum's Json
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        axum::extract::Query::<T>::from_request_parts(parts, state)
+            .await
+            .map(|axum::extract::Query(value)| Query(value))
+            .map_err(|rejection: QueryRejection| ServerError(ApiError::InvalidQuery { message: rejection.body_text() }))
+    }
+}
+
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match axum::extract::Path::<T>::from_request_parts(parts, state).await {
+            Ok(axum::extract::Path(value)) => Ok(Path(value)),
+            // Only happens if a route and its handler disagree, which is on us
+            Err(rejection @ PathRejection::MissingPathParams(_)) => Err(ServerError::internal(rejection)),
+            Err(rejection) => Err(ServerError(ApiError::InvalidPath { message: rejection.body_text() }))
+        }
+    }
+}
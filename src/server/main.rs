@@ -1,28 +1,44 @@
-use tokio;
 use axum::{
     Extension,
     middleware,
     middleware::Next,
-    extract::{Request, FromRequestParts},
-    routing::{get, post},
+    extract::{Request, FromRequestParts, State},
+    routing::{delete, get, post, put},
     Router,
-    Json,
-    http::{StatusCode, request::Parts, header::AUTHORIZATION},
-    response::IntoResponse,
+    http::{request::Parts, header::AUTHORIZATION},
+    response::{IntoResponse, Response},
     async_trait
 };
 use tower::ServiceBuilder;
 use headers::{Header, authorization::{Authorization, Bearer}};
-use reqwest;
-use reqwest::Method;
-use std::{env, thread, fs, fs::File, path::Path, io::Write, time::Duration, collections::HashMap};
-use itertools::Itertools;
 use tower_http::{cors::CorsLayer, services::ServeDir};
-use chrono::{DateTime, Utc};
 use clap::Parser;
-use ormos::messages::*;
-use sqlx::postgres::{PgPoolOptions, PgPool};
+use ormos::{messages::*, validation};
 use serde::Deserialize;
+use accounts::CurrentUser;
+use commanders::Commanders;
+use playgroups::CurrentPlaygroup;
+use error::{ServerError, ServerResult};
+use extract::{Json, Path, Query};
+use store::{NewGame, SharedStore, StoreError};
+
+mod accounts;
+mod api_tokens;
+mod audit;
+mod commanders;
+mod error;
+mod extract;
+mod playgroups;
+mod ratings;
+mod scoring;
+mod seasons;
+mod stats;
+mod store;
+#[cfg(test)]
+mod tests;
+
+// Most games GET /api/games hands out at once, also what you get without a limit
+const MAX_GAMES_PAGE: usize = 100;
 
 #[derive(Parser, Debug)]
 struct CliOptions {
@@ -39,202 +55,118 @@ struct CliOptions {
     static_dir: String,
 }
 
-#[derive(Deserialize)]
-struct ScryfallLegalities {
-    commander: String
-}
-
-#[derive(Deserialize)]
-struct ScryfallPart {
-    component: String,
-    name: String
-}
-
-#[derive(Deserialize)]
-struct ScryfallCardFace {
-    type_line: Option<String>,
-    name: String
-}
-
-#[derive(Deserialize)]
-struct ScryfallCard {
-    type_line: Option<String>,
-    name: String,
-    legalities: ScryfallLegalities,
-    games: Vec<String>,
-    oracle_text: Option<String>,
-    all_parts: Option<Vec<ScryfallPart>>,
-    card_faces: Option<Vec<ScryfallCardFace>>
-}
-
-fn generate_commanders() {
-    println!("Getting bulk data URI");
-    let bulk_data_response: BulkDataResponse = reqwest::blocking::get("https://api.scryfall.com/bulk-data/default-cards")
-        .unwrap()
-        .json()
-        .unwrap();
-
-    println!("Getting bulk data from {}", bulk_data_response.download_uri);
-    let cards: Vec<ScryfallCard> = reqwest::blocking::Client::new()
-        .request(Method::GET, bulk_data_response.download_uri)
-        .timeout(Duration::from_secs(60 * 20))
-        .send()
-        .unwrap()
-        .json()
-        .unwrap();
-
-    let mut commanders: Vec<String> = Vec::new();
-
-    for card in cards {
-        let mut type_line = card.type_line;
-        let mut name = card.name;
-
-        if card.legalities.commander != "legal" {
-            continue
-        }
-
-        if !card.games.contains(&String::from("paper")) {
-            continue
-        }
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let commanders = Commanders::from_file();
+    let _commander_thread = commanders.spawn_refresh();
 
-        // Skip stuff like Brisela
-        if let Some(parts) = card.all_parts {
-            let meld_result_parts: Vec<&ScryfallPart> = parts.iter().filter(|part| part.component == "meld_result").collect();
-            if !meld_result_parts.is_empty() {
-                if meld_result_parts[0].name == *name {
-                    continue
-                }
-            }
-        }
+    let opts = CliOptions::parse();
 
-        if let Some(faces) = card.card_faces {
-            if let Some(inner_type_line) = &faces[0].type_line {
-                type_line = Some(inner_type_line.clone());
-            }
-            name = faces[0].name.clone();
-        }
+    let store = store::connect().await?;
 
+    accounts::setup_admin(&*store).await?;
 
-        if let Some(type_line) = type_line {
-            if commanders.contains(&name) {
-                continue
-            }
-            if type_line.contains("Creature") && type_line.contains("Legendary") {
-                commanders.push(name);
-            }
-            else if type_line.contains("Background") {
-                commanders.push(name);
-            }
-            else if name == "Grist, the Hunger Tide" {
-                commanders.push(name);
-            }
-            else if let Some(oracle_text) = card.oracle_text {
-                if oracle_text.contains("can be your commander") {
-                    commanders.push(name);
-                }
-            }
+    // Picks up games from before ratings existed, anything
+    // changed by hand needs a POST to /api/ratings/rebuild
+    for playgroup_id in store.playgroup_ids().await? {
+        if let Some(from) = store.unrated_since(playgroup_id).await? {
+            store.rebuild_ratings(playgroup_id, Some(from)).await?;
         }
     }
 
-    commanders.sort();
+    let app = app(store, commanders)
+        .fallback_service(
+            ServeDir::new(opts.static_dir)
+            );
 
-    let mut file = File::create("commanders.json").unwrap();
-    let s = serde_json::to_string(&commanders).unwrap();
-    write!(file, "{}", s).unwrap();
+    // run our app with hyper, listening globally on port 3000
+    let listener = tokio::net::TcpListener::bind(format!("{}:{}", opts.addr, opts.port)).await.unwrap();
+    axum::serve(listener, app).await.unwrap();
 
-    println!("Loaded {} commanders", commanders.len());
+    Ok(())
 }
 
-fn get_post_token() -> String {
-    env::var("POST_TOKEN").unwrap_or(String::from("password"))
+// Everything under /api, without the static files so tests can drive it
+fn app(store: SharedStore, commanders: Commanders) -> Router {
+    // The same API is served for every playgroup under /api/groups/<slug>
+    // and for the default group straight under /api like it always was
+    let admin_apis = Router::new()
+        .route("/users", get(accounts::get_users).post(accounts::post_user))
+        .route("/users/:name/revoke", post(accounts::post_revoke_user))
+        .route("/users/:name/tokens", get(api_tokens::get_user_api_tokens))
+        .route("/groups", post(playgroups::post_playgroup))
+        .route_layer(middleware::from_fn_with_state(Some(Scope::Admin), user_auth));
+
+    // Everyone manages their own tokens after logging in, see api_tokens
+    let token_apis = Router::new()
+        .route("/tokens", get(api_tokens::get_api_tokens).post(api_tokens::post_api_token))
+        .route("/tokens/:id", delete(api_tokens::delete_api_token))
+        .route_layer(middleware::from_fn_with_state(None, user_auth));
+
+    Router::new()
+        .route("/api/login", post(accounts::post_login))
+        .route("/api/logout", post(accounts::post_logout))
+        .route("/api/groups", get(playgroups::get_playgroups))
+        .nest("/api", admin_apis)
+        .nest("/api", token_apis)
+        .nest("/api/groups/:group", api_routes())
+        .nest("/api", api_routes())
+        .layer(
+            ServiceBuilder::new()
+                .layer(Extension(store))
+                .layer(Extension(commanders))
+                .layer(CorsLayer::permissive())
+            )
 }
 
-#[tokio::main]
-async fn main() -> Result<(), sqlx::Error> {
-    let _commander_thread = thread::spawn(|| {
-        // This is just to prevent downloading every time
-        // the program runs during development, but still
-        // allows a fresh server to download immediately
-        if !Path::new("commanders.json").exists() {
-            generate_commanders();
-        }
-        loop {
-            thread::sleep(Duration::from_secs(60 * 60 * 24));
-            generate_commanders();
-        }
-    });
-
-    let opts = CliOptions::parse();
-
-    // Defaults values correspond to development postgres, not production
-    let pg_user = env::var("POSTGRES_USER").unwrap_or(String::from("postgres"));
-    let pg_password = env::var("POSTGRES_PASSWORD").unwrap_or(String::from("password"));
-    let pg_host = env::var("POSTGRES_HOST").unwrap_or(String::from("localhost"));
-    let pg_port = env::var("POSTGRES_PORT").unwrap_or(String::from("55432"));
-    let pg_database = env::var("POSTGRES_DB").unwrap_or(String::from("ormos"));
-
-    let connection_string = format!("postgres://{}:{}@{}:{}/{}", pg_user, pg_password, pg_host, pg_port, pg_database);
-
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(connection_string.as_str()).await?;
-
-    sqlx::query("CREATE TABLE IF NOT EXISTS players (
-            id SERIAL PRIMARY KEY,
-            name TEXT UNIQUE NOT NULL
-            )").execute(&pool).await?;
-
-    sqlx::query("CREATE TABLE IF NOT EXISTS games (
-            id SERIAL PRIMARY KEY,
-            start_datetime TIMESTAMP WITH TIME ZONE NOT NULL,
-            end_datetime TIMESTAMP WITH TIME ZONE NOT NULL
-            )").execute(&pool).await?;
-
-    sqlx::query("CREATE TABLE IF NOT EXISTS games_players (
-            id SERIAL PRIMARY KEY,
-            game_id INTEGER NOT NULL,
-            player_id INTEGER NOT NULL,
-            rank INTEGER NOT NULL,
-            FOREIGN KEY (game_id) REFERENCES games(id),
-            FOREIGN KEY (player_id) REFERENCES players(id))").execute(&pool).await?;
-
-    sqlx::query("CREATE TABLE IF NOT EXISTS commanders (
-            id SERIAL PRIMARY KEY,
-            games_players_id INTEGER NOT NULL,
-            commander TEXT NOT NULL,
-            FOREIGN KEY (games_players_id) REFERENCES games_players(id)
-            )").execute(&pool).await?;
-
-
-
-    let post_apis = Router::new()
+fn api_routes() -> Router {
+    // Each set of writes needs its own scope, see bearer_auth
+    let game_apis = Router::new()
         .route("/games", post(post_games))
+        .route("/games/:id", put(put_game).patch(patch_game).delete(delete_game))
+        .route("/games/:id/restore", post(restore_game))
+        .route_layer(middleware::from_fn_with_state(Scope::GamesWrite, bearer_auth));
+
+    let player_apis = Router::new()
         .route("/players", post(post_player))
-        .layer(middleware::from_fn(bearer_auth));
+        .route_layer(middleware::from_fn_with_state(Scope::PlayersWrite, bearer_auth));
+
+    let admin_apis = Router::new()
+        .route("/ratings/rebuild", post(ratings::post_rebuild))
+        .route("/seasons", post(seasons::post_season))
+        .route("/seasons/:id", put(seasons::put_season).delete(seasons::delete_season))
+        .route("/scoring", post(scoring::post_scheme))
+        .route("/scoring/:id", put(scoring::put_scheme).delete(scoring::delete_scheme))
+        .route("/members", get(accounts::get_members).post(accounts::post_member))
+        .route("/members/:name", delete(accounts::delete_member))
+        .route("/audit", get(audit::get_audit))
+        .route_layer(middleware::from_fn_with_state(Scope::Admin, bearer_auth));
 
     let get_apis = Router::new()
         .route("/games", get(get_games))
+        .route("/games/:id", get(get_game))
         .route("/players", get(get_players))
-        .route("/commanders", get(get_commanders));
-
-    // build our application with a single route
-    let app = Router::new()
-        .nest("/api", post_apis)
-        .nest("/api", get_apis)
-        .layer(
-            ServiceBuilder::new()
-                .layer(Extension(pool))
-                .layer(CorsLayer::permissive())
-            )
-        .fallback_service(
-            ServeDir::new(opts.static_dir)
-            );
-
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind(format!("{}:{}", opts.addr, opts.port)).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
-
-    Ok(())
+        .route("/players/:name/stats", get(stats::get_player_stats))
+        .route("/players/:name/history", get(stats::get_player_history))
+        .route("/players/:name/ratings", get(ratings::get_rating_history))
+        .route("/head-to-head", get(stats::get_head_to_head))
+        .route("/ratings", get(ratings::get_leaderboard))
+        .route("/seasons", get(seasons::get_seasons))
+        .route("/seasons/:id", get(seasons::get_season))
+        .route("/seasons/:id/leaderboard", get(seasons::get_season_leaderboard))
+        .route("/scoring", get(scoring::get_schemes))
+        .route("/scoring/:id", get(scoring::get_scheme))
+        .route("/scoring/:id/leaderboard", get(scoring::get_points_leaderboard))
+        .route("/commanders", get(commanders::get_commanders))
+        .route("/commanders/stats", get(stats::get_commanders_stats))
+        .route("/commanders/:name/stats", get(stats::get_commander_stats));
+
+    // Layers run outside in, so the playgroup is known by the time bearer_auth checks membership
+    game_apis
+        .merge(player_apis)
+        .merge(admin_apis)
+        .merge(get_apis)
+        .route_layer(middleware::from_fn(playgroups::resolve_playgroup))
 }
 
 struct BearerAuthWithJsonResponse (Authorization<Bearer>);
@@ -242,16 +174,13 @@ struct BearerAuthWithJsonResponse (Authorization<Bearer>);
 // We implement our own extractor because TypedHeader
 // returns plaintext error messages rather than JSON
 // and we want the errors to be machine readable.
-//
-// TODO: Make a custom extractor for JSON as well
-// because JSON that fails to deserialze will also
-// return plaintext error messages
+// The same goes for the ones in extract.
 #[async_trait]
 impl<S> FromRequestParts<S> for BearerAuthWithJsonResponse
 where
     S: Send + Sync,
 {
-    type Rejection = (StatusCode, Json<BearerAuthFailureResponse>);
+    type Rejection = ServerError;
 
     // This is largely copied from https://docs.rs/axum-extra/latest/src/axum_extra/typed_header.rs.html#59-81
     async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
@@ -261,276 +190,175 @@ where
             .map(Self)
             .map_err(|_| {
                 if is_missing {
-                    (StatusCode::UNAUTHORIZED, Json(BearerAuthFailureResponse{
-                        success:false, error: String::from("Missing bearer token in Authorization header.")
-                    }))
+                    ServerError(ApiError::MissingToken)
                 }
                 else {
-                    (StatusCode::UNAUTHORIZED, Json(BearerAuthFailureResponse{
-                        success:false, error: String::from("Invalid bearer token.")
-                    }))
+                    ServerError(ApiError::MalformedToken)
                 }
             })
     }
 }
 
-async fn bearer_auth(bearer: BearerAuthWithJsonResponse, request: Request, next: Next) -> impl IntoResponse {
-    if bearer.0.token() == get_post_token() {
-        let response = next.run(request).await;
-        (StatusCode::OK, response)
-    }
-    else {
-        (StatusCode::UNAUTHORIZED, Json(BearerAuthFailureResponse {
-            success: false,
-            error: String::from("Incorrect bearer token provided.")
-        }).into_response())
+// Any logged in user will do without a scope
+async fn authenticate_bearer(store: &SharedStore, bearer: &BearerAuthWithJsonResponse, scope: Option<Scope>) -> ServerResult<CurrentUser> {
+    match (accounts::authenticate(&**store, bearer.0.token()).await?, scope) {
+        (Some(user), Some(scope)) if !user.has_scope(scope) => Err(ApiError::MissingScope { scope }.into()),
+        (Some(user), _) => Ok(user),
+        (None, _) => Err(ApiError::InvalidToken.into())
     }
 }
 
-async fn post_games(Extension(pool): Extension<PgPool>, Json(payload): Json<CreateGamePayload>) -> impl IntoResponse {
-    if payload.players.len() < 2 {
-        return (StatusCode::BAD_REQUEST, Json(
-                    PostResponse {
-                        success: false,
-                        error: Some(String::from("A game must have at least two players"))
-                    }
-                ));
+// For routes outside of a playgroup that only need the right scope, if any
+async fn user_auth(State(scope): State<Option<Scope>>, Extension(store): Extension<SharedStore>, bearer: BearerAuthWithJsonResponse, mut request: Request, next: Next) -> Response {
+    match authenticate_bearer(&store, &bearer, scope).await {
+        Ok(user) => {
+            request.extensions_mut().insert(user);
+            next.run(request).await
+        },
+        Err(error) => error.into_response()
     }
+}
 
-    let mut player_counts = HashMap::<String, i32>::new();
+// Writes to a playgroup need a token with the route's scope whose user
+// is a member of the playgroup, see playgroups::resolve_playgroup
+async fn bearer_auth(State(scope): State<Scope>, Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, bearer: BearerAuthWithJsonResponse, mut request: Request, next: Next) -> Response {
+    let user = match authenticate_bearer(&store, &bearer, Some(scope)).await {
+        Ok(user) => user,
+        Err(error) => return error.into_response()
+    };
 
-    for player in payload.players.iter() {
-        player_counts.entry(player.name.clone()).and_modify(|counter| *counter += 1).or_insert(1);
+    match accounts::can_write(&*store, &user, group.id).await {
+        Ok(true) => {},
+        Ok(false) => return ServerError(ApiError::NotAMember { username: user.username, playgroup: group.slug }).into_response(),
+        Err(error) => return ServerError::from(error).into_response()
     }
 
-    for player_count in player_counts.into_values() {
-        if player_count > 1 {
-            return (StatusCode::BAD_REQUEST, Json(
-                PostResponse {
-                    success: false,
-                    error: Some(String::from("Cannot have the same player multiple times"))
-                }));
-        }
-    }
+    request.extensions_mut().insert(user);
 
-    let start_datetime = DateTime::parse_from_rfc3339(&payload.start_datetime).unwrap();
-    let end_datetime = DateTime::parse_from_rfc3339(&payload.end_datetime).unwrap();
+    // Pass the handler's response through untouched so
+    // its status code (400, 404, ...) makes it to the client
+    next.run(request).await
+}
 
+// Every route is also served under /api/groups/:group, so path
+// parameters are picked out by name rather than by position
+#[derive(Deserialize)]
+pub struct IdPath {
+    pub id: i32
+}
 
-    if end_datetime <= start_datetime {
-        return (StatusCode::BAD_REQUEST, Json(
-            PostResponse {
-                success: false,
-                error: Some(String::from("End datetime cannot be earlier than or equal to start datetime"))
-            }));
-    }
+#[derive(Deserialize)]
+pub struct NamePath {
+    pub name: String
+}
 
-    let sorted_ranks = payload.players
-        .iter()
-        .map(|player| player.rank)
-        .sorted();
-
-    // If any ranks are outside the bounds of 1-<number of players>
-    // then the ranking is invalid
-    for player in payload.players.iter() {
-        if player.rank < 1 || player.rank > payload.players.len() {
-            return (StatusCode::BAD_REQUEST, Json(
-                PostResponse {
-                    success: false,
-                    error: Some(format!("Player {} has invalid value for rank", player.name))
-                }));
-        }
-    }
+async fn post_games(Extension(store): Extension<SharedStore>, Extension(commanders): Extension<Commanders>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Query(query): Query<GameWriteQuery>, Json(payload): Json<CreateGamePayload>) -> ServerResult<Json<PostResponse>> {
+    // The checks live in ormos::validation so the client can run them too
+    validation::validate_game(&payload)?;
+    commanders.check(&payload.players, &query)?;
 
-    // The way we validate ranks is by ensuring that the first in sorted_ranks
-    // rank is 1. After that we ensure the second rank is either equal to the
-    // previous or equal to 2, then we check that third rank in order is equal to
-    // 3 or the previous rank and so on.
-
-    let enumerated_pairs = sorted_ranks
-        .tuple_windows()
-        .enumerate()
-        .map(|(index, (prev, rank))| (index + 2, (prev, rank)))
-        .collect::<Vec<(usize, (usize, usize))>>();
-
-    // If prev in the first tuple is not 1 that's a problem
-    if enumerated_pairs[0].1.0 != 1 {
-        return (StatusCode::BAD_REQUEST, Json(
-            PostResponse {
-                success: false,
-                error: Some(String::from("At least one player must come in first"))
-            }));
-    }
+    store.create_game(group.id, user.id, NewGame {
+        start_datetime: payload.start_datetime,
+        end_datetime: payload.end_datetime,
+        players: payload.players
+    }).await?;
 
-    // After the first prev is validated as being 1
-    // we can compare cur to prev and the index of this pair
-    // The only gotcha is that we have to start index at 2
-    // because rankings start at 1 and due to the way tuple_windows
-    // makes pairs the first cur is actually the second element in the list
-    for (index, (prev, cur)) in enumerated_pairs {
-        if index != cur && prev != cur {
-            return (StatusCode::BAD_REQUEST, Json(
-                PostResponse {
-                    success: false,
-                    error: Some(String::from(format!("Ranking is invalid player with a rank {} should have rank {} or {}", cur, index, prev)))
-                }));
-        }
-    }
+    Ok(Json(PostResponse { success: true, error: None }))
+}
 
-    for player in payload.players.iter() {
-        if player.commanders.is_empty() {
-            return (StatusCode::BAD_REQUEST, Json(
-                    PostResponse {
-                        success: false,
-                        error: Some(format!("Player \"{}\" has no commanders", player.name))
-                    }));
-        }
-        for commander in player.commanders.clone() {
-            if commander == "" {
-                return (StatusCode::BAD_REQUEST, Json(
-                    PostResponse {
-                        success: false,
-                        error: Some(format!("Player \"{}\" has an empty string as a commander", player.name))
-                    }));
-            }
-        }
-    }
+// Both PUT and PATCH end up here. PUT has to send every field,
+// PATCH can leave out any of them to keep what is already stored,
+// see Store::update_game for how the two are put together.
+async fn update_game(store: SharedStore, commanders: Commanders, group: CurrentPlaygroup, user: CurrentUser, id: i32, query: GameWriteQuery, payload: UpdateGamePayload) -> ServerResult<Json<PostResponse>> {
+    let not_found = || ServerError(ApiError::GameNotFound { id });
 
-    // END VALIDATION
+    // Only so a missing game is reported as such rather than as a bad commander
+    store.game(group.id, id).await?.ok_or_else(not_found)?;
 
-    let mut tx = pool.begin().await.unwrap();
+    // Stored commanders were checked when they were written, or let
+    // through on purpose, so only new ones are checked again
+    if let Some(players) = &payload.players {
+        commanders.check(players, &query)?;
+    }
 
-    let row: (i32, ) = sqlx::query_as("INSERT INTO games (start_datetime, end_datetime) VALUES($1, $2) RETURNING id").bind(start_datetime).bind(end_datetime).fetch_one(&mut *tx).await.unwrap();
-    let game_id = row.0;
+    store.update_game(group.id, user.id, id, payload).await?.ok_or_else(not_found)?;
 
-    for player in payload.players {
-        let player_row_result: Result<(i32, ), sqlx::Error> = sqlx::query_as("SELECT id FROM players WHERE name = $1").bind(&player.name).fetch_one(&mut *tx).await;
+    Ok(Json(PostResponse { success: true, error: None }))
+}
 
-        match player_row_result {
-            Ok(player_row) => {
-                let player_id = player_row.0;
-                // TODO: Handle potential error instead of unwrapping
-                let row: (i32,) = sqlx::query_as("INSERT INTO games_players (game_id, player_id, rank) VALUES($1, $2, $3) RETURNING id").bind(game_id).bind(player_id).bind(player.rank as i32).fetch_one(&mut *tx).await.unwrap();
-                let games_players_id = row.0;
-                for commander in player.commanders {
-                    sqlx::query("INSERT INTO commanders (games_players_id, commander) VALUES($1, $2)").bind(games_players_id).bind(commander).execute(&mut *tx).await.unwrap();
-                }
-            },
-            Err(error) => {
-                return (StatusCode::BAD_REQUEST, Json(PostResponse{ 
-                    success: false,
-                    error: Some(error.to_string())
-                }));
-            }
-        }
-    }
-    tx.commit().await.unwrap();
+async fn put_game(Extension(store): Extension<SharedStore>, Extension(commanders): Extension<Commanders>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Path(IdPath { id }): Path<IdPath>, Query(query): Query<GameWriteQuery>, Json(payload): Json<CreateGamePayload>) -> ServerResult<Json<PostResponse>> {
+    update_game(store, commanders, group, user, id, query, UpdateGamePayload {
+        start_datetime: Some(payload.start_datetime),
+        end_datetime: Some(payload.end_datetime),
+        players: Some(payload.players)
+    }).await
+}
 
-    (StatusCode::OK, Json(PostResponse { success:true, error: None }))
+async fn patch_game(Extension(store): Extension<SharedStore>, Extension(commanders): Extension<Commanders>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Path(IdPath { id }): Path<IdPath>, Query(query): Query<GameWriteQuery>, Json(payload): Json<UpdateGamePayload>) -> ServerResult<Json<PostResponse>> {
+    update_game(store, commanders, group, user, id, query, payload).await
 }
 
-async fn post_player(Extension(pool): Extension<PgPool>, Json(payload): Json<PlayerPayload>) -> impl IntoResponse {
-    match sqlx::query("INSERT INTO players (name) VALUES($1)").bind(payload.name).execute(&pool).await {
-        Ok(_) => (StatusCode::OK, Json(PostResponse { success: true, error: None})),
-        Err(error) if error.as_database_error().unwrap().code().unwrap() == "23505" => {
-            (StatusCode::BAD_REQUEST, Json(
-                    PostResponse{
-                        success: false,
-                        error: Some(String::from("Player already exists"))
-                    }
-                    ))
-        }
-        Err(error) => (StatusCode::BAD_REQUEST, Json(
-                    PostResponse {
-                        success: false,
-                        error:  Some(error.to_string())
-                    }
-                ))
+async fn delete_game(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Path(IdPath { id }): Path<IdPath>) -> ServerResult<Json<PostResponse>> {
+    if store.delete_game(group.id, user.id, id).await?.is_none() {
+        return Err(ApiError::GameNotFound { id }.into());
     }
-}
 
-async fn get_games(Extension(pool): Extension<PgPool>) -> Json<GamesResponse> {
-    let mut games_response = GamesResponse{
-        games: vec![]
-    };
+    Ok(Json(PostResponse { success: true, error: None }))
+}
 
-    let rows: Vec<(i32, i32, DateTime<Utc>, DateTime<Utc>, String, i32)> = sqlx::query_as("SELECT games.id, games_players.id, start_datetime, end_datetime, players.name, rank FROM games_players INNER JOIN games ON game_id = games.id INNER JOIN players ON player_id = players.id").fetch_all(&pool).await.unwrap();
+async fn restore_game(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Path(IdPath { id }): Path<IdPath>) -> ServerResult<Json<PostResponse>> {
+    if store.restore_game(group.id, user.id, id).await?.is_none() {
+        return Err(ApiError::GameNotDeleted { id }.into());
+    }
 
-    let commander_rows: Vec<(i32, String)> = sqlx::query_as("SELECT games_players.id, commander FROM commanders INNER JOIN games_players ON games_players_id = games_players.id").fetch_all(&pool).await.unwrap();
+    Ok(Json(PostResponse { success: true, error: None }))
+}
 
-    let unique_game_ids = rows.iter().fold(Vec::new(), |mut acc, row| {
-        if !acc.contains(&row.0) {
-            acc.push(row.0);
-        }
+async fn post_player(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Json(payload): Json<PlayerPayload>) -> ServerResult<Json<PostResponse>> {
+    match store.create_player(group.id, user.id, &payload.name).await {
+        Ok(()) => Ok(Json(PostResponse { success: true, error: None })),
+        Err(StoreError::Duplicate) => Err(ApiError::PlayerExists { player: payload.name }.into()),
+        Err(error) => Err(error.into())
+    }
+}
 
-        acc
-    });
-
-    let games_players_id_to_commanders = commander_rows.iter().fold(HashMap::new(), |mut acc: HashMap<i32, Vec<String>>, row| {
-        match acc.get_mut(&row.0) {
-            Some(commanders) => {
-                commanders.push(row.1.clone());
-            },
-            None => {
-                acc.insert(row.0, vec![row.1.clone()]);
-            }
+async fn get_games(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Query(query): Query<GamesQuery>) -> ServerResult<Json<GamesResponse>> {
+    // A cursor from another playgroup or a game that's since been deleted
+    // would otherwise quietly give an empty page
+    if let Some(before) = query.before {
+        if store.game(group.id, before).await?.is_none() {
+            return Err(ApiError::UnknownCursor { before }.into());
         }
+    }
 
-        acc
-    });
-
-    for id in unique_game_ids {
-        let game_rows: Vec<&(i32, i32, DateTime<Utc>, DateTime<Utc>, String, i32)> = rows.iter().filter(|row| {
-            row.0 == id
-        }).collect();
-
-        let mut players: Vec<Player> = Vec::new();
-        let start_datetime = game_rows[0].2.clone();
-        let end_datetime = game_rows[0].3.clone();
+    let limit = query.limit.unwrap_or(MAX_GAMES_PAGE).min(MAX_GAMES_PAGE);
+    let games = store.games(group.id, &GamesQuery { limit: Some(limit), ..query }).await?;
 
-        for game_row in game_rows {
-            players.push(Player{
-                name: game_row.4.clone(),
-                commanders: games_players_id_to_commanders.get(&game_row.1).unwrap().clone(),
-                rank: game_row.5 as usize
-            })
-        }
+    // Only hand out a cursor when the page is full, otherwise there's nothing left
+    let next_cursor = if limit > 0 && games.len() == limit {
+        games.last().map(|game| game.id)
+    } else {
+        None
+    };
 
-        let game = Game{
-            start_datetime,
-            end_datetime,
-            players
-        };
+    Ok(Json(GamesResponse {
+        games,
+        next_cursor
+    }))
+}
 
-        games_response.games.push(game); 
+async fn get_game(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Path(IdPath { id }): Path<IdPath>) -> ServerResult<Json<GameResponse>> {
+    match store.game(group.id, id).await? {
+        Some(game) => Ok(Json(GameResponse { game })),
+        None => Err(ApiError::GameNotFound { id }.into())
     }
-
-
-    Json(games_response)
 }
 
-async fn get_players(Extension(pool): Extension<PgPool>) -> Json<PlayersResponse> {
-    let rows: Vec<(String,)> = sqlx::query_as("SELECT name FROM players").fetch_all(&pool).await.unwrap();
-
-    // Flatten rows
-    let names = rows.iter().fold(Vec::new(), |mut acc, row| {
-        acc.push(row.0.clone());
-        acc
-    });
-
+async fn get_players(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>) -> ServerResult<Json<PlayersResponse>> {
     let players_response = PlayersResponse{
-        names
+        names: store.players(group.id).await?
     };
 
 
-    Json(players_response)
-}
-
-async fn get_commanders() -> Json<CommandersResponse> {
-    let json_text = fs::read_to_string("commanders.json").unwrap();
-    let cards: Vec<String> = serde_json::from_str(&json_text).expect("JSON was not well-formatted");
-
-    Json(CommandersResponse {
-        commanders: cards
-    })
+    Ok(Json(players_response))
 }
@@ -0,0 +1,78 @@
+use axum::{
+    Extension,
+    extract::{RawPathParams, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ormos::messages::*;
+use crate::{accounts::{CurrentUser, require_admin}, error::{ServerError, ServerResult}, extract::Json, store::{SharedStore, StoreError}};
+
+// Data from before playgroups existed lives in this group,
+// it's also what the routes without a group prefix use
+pub const DEFAULT_SLUG: &str = "default";
+
+// The playgroup a request is for, put into the request by resolve_playgroup
+#[derive(Clone, Debug)]
+pub struct CurrentPlaygroup {
+    pub id: i32,
+    pub slug: String,
+}
+
+// Works out which group the request is for from the :group part of the
+// path, falling back to the default group for the unprefixed routes.
+// Runs as a route layer so the path parameters are already known.
+pub async fn resolve_playgroup(Extension(store): Extension<SharedStore>, params: RawPathParams, mut request: Request, next: Next) -> Response {
+    let slug = params
+        .iter()
+        .find(|(key, _)| *key == "group")
+        .map(|(_, value)| value.to_string())
+        .unwrap_or(String::from(DEFAULT_SLUG));
+
+    match store.playgroup(&slug).await {
+        Ok(Some(group)) => {
+            request.extensions_mut().insert(group);
+            next.run(request).await
+        },
+        Ok(None) => ServerError(ApiError::PlaygroupNotFound { slug }).into_response(),
+        Err(error) => ServerError::from(error).into_response()
+    }
+}
+
+fn validate_playgroup(payload: &PlaygroupPayload) -> ServerResult<()> {
+    let valid_slug = !payload.slug.is_empty()
+        && payload.slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+
+    if !valid_slug {
+        return Err(ApiError::InvalidSlug { slug: payload.slug.clone() }.into());
+    }
+
+    if payload.name.trim().is_empty() {
+        return Err(ApiError::MissingField { field: String::from("name") }.into());
+    }
+
+    Ok(())
+}
+
+pub async fn get_playgroups(Extension(store): Extension<SharedStore>) -> ServerResult<Json<PlaygroupsResponse>> {
+    Ok(Json(PlaygroupsResponse {
+        groups: store.playgroups().await?
+    }))
+}
+
+// Only admins can create groups, members are added with post_member
+pub async fn post_playgroup(Extension(store): Extension<SharedStore>, Extension(user): Extension<CurrentUser>, Json(payload): Json<PlaygroupPayload>) -> ServerResult<Json<PlaygroupResponse>> {
+    require_admin(&user)?;
+
+    validate_playgroup(&payload)?;
+
+    match store.create_playgroup(&payload.slug, &payload.name).await {
+        Ok(_) => Ok(Json(PlaygroupResponse {
+            group: Playgroup {
+                slug: payload.slug,
+                name: payload.name,
+            }
+        })),
+        Err(StoreError::Duplicate) => Err(ApiError::PlaygroupExists { slug: payload.slug }.into()),
+        Err(error) => Err(ServerError::from(error))
+    }
+}
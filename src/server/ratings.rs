@@ -0,0 +1,180 @@
+use axum::Extension;
+use std::{cmp::Ordering, collections::{BTreeMap, HashMap}};
+use chrono::{DateTime, Utc};
+use ormos::messages::*;
+use crate::{NamePath, accounts::{CurrentUser, require_admin}, error::ServerResult, extract::{Json, Path, Query}, playgroups::CurrentPlaygroup, seasons::{resolve_season, season_games}, store::{SharedStore, Store, StoreError}};
+
+// Where every player starts out
+pub const INITIAL_RATING: f64 = 1500.0;
+
+// The most a single game can move a rating
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Clone, Debug)]
+pub struct RatingChange {
+    pub game_id: i32,
+    pub player_id: i32,
+    pub rating_before: f64,
+    pub rating_after: f64,
+}
+
+// Multiplayer Elo. A game with N players is scored as the N - 1 head to head
+// matchups each player had, finishing ahead counts as a win, behind as a loss
+// and sharing a rank as a draw. The result is scaled by N - 1 so a single
+// game moves a rating by at most K_FACTOR no matter how big the pod was.
+//
+// Games are replayed oldest first, so the same games always
+// produce the same ratings regardless of when they were entered.
+pub fn compute_ratings(games: &[Game]) -> Vec<RatingChange> {
+    continue_ratings(games, HashMap::new())
+}
+
+// Picks up after games that were already rated, ratings has each player's
+// rating going into the first of these games. Anyone missing starts fresh.
+pub fn continue_ratings(games: &[Game], mut ratings: HashMap<i32, f64>) -> Vec<RatingChange> {
+    let mut games: Vec<&Game> = games.iter().collect();
+    games.sort_by_key(|game| (game.start_datetime, game.id));
+
+    let mut changes = Vec::new();
+
+    for game in games {
+        let players: Vec<(i32, usize)> = game.players
+            .iter()
+            .filter_map(|player| player.id.map(|id| (id, player.rank)))
+            .collect();
+
+        if players.len() < 2 {
+            continue
+        }
+
+        let before: Vec<f64> = players
+            .iter()
+            .map(|(id, _)| ratings.get(id).copied().unwrap_or(INITIAL_RATING))
+            .collect();
+
+        for (index, (id, rank)) in players.iter().enumerate() {
+            let mut delta = 0.0;
+
+            for (other_index, (_, other_rank)) in players.iter().enumerate() {
+                if index == other_index {
+                    continue
+                }
+
+                let expected = 1.0 / (1.0 + 10f64.powf((before[other_index] - before[index]) / 400.0));
+                let actual = match rank.cmp(other_rank) {
+                    Ordering::Less => 1.0,
+                    Ordering::Equal => 0.5,
+                    Ordering::Greater => 0.0,
+                };
+                delta += actual - expected;
+            }
+
+            let rating_after = before[index] + K_FACTOR * delta / (players.len() - 1) as f64;
+            ratings.insert(*id, rating_after);
+
+            changes.push(RatingChange {
+                game_id: game.id,
+                player_id: *id,
+                rating_before: before[index],
+                rating_after,
+            });
+        }
+    }
+
+    changes
+}
+
+// players.name, games.id, start_datetime, rating_before, rating_after
+pub type RatingRow = (String, i32, DateTime<Utc>, f64, f64);
+
+// Puts names and dates back on computed rating changes so they
+// look the same as the rows stored in the ratings table
+pub fn rating_rows(games: &[Game], changes: &[RatingChange]) -> Vec<RatingRow> {
+    let games: HashMap<i32, &Game> = games.iter().map(|game| (game.id, game)).collect();
+
+    changes
+        .iter()
+        .filter_map(|change| {
+            let game = games.get(&change.game_id)?;
+            let player = game.players.iter().find(|player| player.id == Some(change.player_id))?;
+            Some((player.name.clone(), game.id, game.start_datetime, change.rating_before, change.rating_after))
+        })
+        .collect()
+}
+
+// Stored ratings cover every game ever played. Within a season ratings
+// start over, so they're computed from just that season's games instead.
+// Either way the rows come back oldest first.
+pub async fn fetch_rating_rows(store: &dyn Store, playgroup_id: i32, season: Option<&Season>, name: Option<&str>) -> Result<Vec<RatingRow>, StoreError> {
+    match season {
+        Some(season) => {
+            let games = store.games(playgroup_id, &season_games(Some(season))).await?;
+            Ok(rating_rows(&games, &compute_ratings(&games))
+                .into_iter()
+                .filter(|(row_name, ..)| name.is_none_or(|name| name == row_name))
+                .collect())
+        },
+        None => store.rating_rows(playgroup_id, name).await
+    }
+}
+
+// Highest rating first, rows have to be oldest first
+pub fn leaderboard(rows: Vec<RatingRow>) -> Vec<RatingEntry> {
+    // The last row seen for a player is their current rating
+    let mut entries = BTreeMap::<String, RatingEntry>::new();
+    for (name, _, _, rating_before, rating_after) in rows {
+        let entry = entries.entry(name.clone()).or_insert(RatingEntry {
+            name,
+            rating: INITIAL_RATING,
+            games: 0,
+            last_change: 0.0,
+        });
+        entry.rating = rating_after;
+        entry.games += 1;
+        entry.last_change = rating_after - rating_before;
+    }
+
+    let mut ratings: Vec<RatingEntry> = entries.into_values().collect();
+    ratings.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+    ratings
+}
+
+pub async fn fetch_rating_history(store: &dyn Store, playgroup_id: i32, season: Option<&Season>, name: &str) -> Result<Vec<RatingPoint>, StoreError> {
+    let rows = fetch_rating_rows(store, playgroup_id, season, Some(name)).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(_, game_id, start_datetime, rating_before, rating_after)| RatingPoint {
+            game_id,
+            start_datetime,
+            rating_before,
+            rating_after,
+        })
+        .collect())
+}
+
+pub async fn get_leaderboard(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Query(season): Query<SeasonQuery>) -> ServerResult<Json<LeaderboardResponse>> {
+    let season = resolve_season(&*store, group.id, &season).await?;
+    let rows = fetch_rating_rows(&*store, group.id, season.as_ref(), None).await?;
+
+    Ok(Json(LeaderboardResponse { ratings: leaderboard(rows) }))
+}
+
+pub async fn get_rating_history(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Path(NamePath { name }): Path<NamePath>, Query(season): Query<SeasonQuery>) -> ServerResult<Json<RatingHistoryResponse>> {
+    if !store.player_exists(group.id, &name).await? {
+        return Err(ApiError::PlayerNotFound { player: name.clone() }.into());
+    }
+
+    let season = resolve_season(&*store, group.id, &season).await?;
+    let history = fetch_rating_history(&*store, group.id, season.as_ref(), &name).await?;
+
+    Ok(Json(RatingHistoryResponse { name, history }))
+}
+
+pub async fn post_rebuild(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>) -> ServerResult<Json<PostResponse>> {
+    require_admin(&user)?;
+
+    store.rebuild_ratings(group.id, None).await?;
+
+    Ok(Json(PostResponse { success: true, error: None }))
+}
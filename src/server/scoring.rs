@@ -0,0 +1,283 @@
+use axum::Extension;
+use std::{cmp::Ordering, collections::BTreeMap};
+use serde::{Serialize, Deserialize};
+use ormos::messages::*;
+use crate::{IdPath, accounts::{CurrentUser, require_admin}, error::{ServerError, ServerResult}, extract::{Json, Path, Query}, playgroups::CurrentPlaygroup, seasons::{resolve_season, season_games}, store::{SharedStore, StoreError}};
+
+// The part of a scheme stored as JSON in scoring_schemes.definition
+#[derive(Serialize, Deserialize)]
+struct SchemeDefinition {
+    tie_rule: TieRule,
+    tables: BTreeMap<usize, Vec<f64>>,
+}
+
+// id, name, definition
+pub type SchemeRow = (i32, String, String);
+
+fn scheme_from_row((id, name, definition): SchemeRow) -> ServerResult<ScoringScheme> {
+    // Only ever written by us from a SchemeDefinition
+    let definition: SchemeDefinition = serde_json::from_str(&definition).map_err(ServerError::internal)?;
+
+    Ok(ScoringScheme {
+        id,
+        name,
+        tie_rule: definition.tie_rule,
+        tables: definition.tables,
+    })
+}
+
+fn validate_scheme(payload: &ScoringSchemePayload) -> ServerResult<String> {
+    if payload.name.trim().is_empty() {
+        return Err(ApiError::MissingField { field: String::from("name") }.into());
+    }
+
+    if payload.tables.is_empty() {
+        return Err(ApiError::NoPointTables.into());
+    }
+
+    for (players, points) in payload.tables.iter() {
+        if *players < 2 {
+            return Err(ApiError::InvalidPodSize { players: *players }.into());
+        }
+        if points.len() != *players {
+            return Err(ApiError::WrongPlaceCount { players: *players, places: points.len() }.into());
+        }
+        if points.iter().any(|points| !points.is_finite()) {
+            return Err(ApiError::InvalidPoints { players: *players }.into());
+        }
+    }
+
+    let definition = SchemeDefinition {
+        tie_rule: payload.tie_rule,
+        tables: payload.tables.clone(),
+    };
+
+    serde_json::to_string(&definition).map_err(ServerError::internal)
+}
+
+fn map_scheme_write_error(error: StoreError, name: &str) -> ServerError {
+    match error {
+        StoreError::Duplicate => ServerError(ApiError::SchemeExists { name: name.to_string() }),
+        _ => ServerError::from(error)
+    }
+}
+
+async fn fetch_scheme(store: &SharedStore, playgroup_id: i32, id: i32) -> ServerResult<ScoringScheme> {
+    match store.scheme(playgroup_id, id).await? {
+        Some(row) => scheme_from_row(row),
+        None => Err(ApiError::SchemeNotFound { id }.into())
+    }
+}
+
+// Points each player in the game scored, None if the scheme has no table for the pod size.
+//
+// post_games only accepts competition style ranks (1, 2, 2, 4), so k players
+// tied on rank r cover places r through r + k - 1.
+pub fn game_points(scheme: &ScoringScheme, game: &Game) -> Option<Vec<(String, f64)>> {
+    let table = scheme.tables.get(&game.players.len())?;
+
+    Some(game.players
+        .iter()
+        .map(|player| {
+            let tied = game.players.iter().filter(|other| other.rank == player.rank).count();
+            let start = player.rank.saturating_sub(1).min(table.len());
+            let end = (start + tied).min(table.len());
+
+            let points = match scheme.tie_rule {
+                TieRule::Split if end > start => table[start..end].iter().sum::<f64>() / (end - start) as f64,
+                TieRule::Highest => table.get(start).copied().unwrap_or_default(),
+                _ => 0.0,
+            };
+
+            (player.name.clone(), points)
+        })
+        .collect())
+}
+
+#[derive(Default)]
+struct PointsTally {
+    points: f64,
+    games: usize,
+    unscored_games: usize,
+}
+
+pub fn points_standings(scheme: &ScoringScheme, games: &[Game]) -> Vec<PointsStanding> {
+    let mut tallies = BTreeMap::<String, PointsTally>::new();
+
+    for game in games {
+        match game_points(scheme, game) {
+            Some(points) => {
+                for (name, points) in points {
+                    let tally = tallies.entry(name).or_default();
+                    tally.points += points;
+                    tally.games += 1;
+                }
+            },
+            None => {
+                for player in game.players.iter() {
+                    tallies.entry(player.name.clone()).or_default().unscored_games += 1;
+                }
+            }
+        }
+    }
+
+    let mut standings: Vec<PointsStanding> = tallies
+        .into_iter()
+        .map(|(name, tally)| PointsStanding {
+            position: 0,
+            name,
+            points: tally.points,
+            games: tally.games,
+            average_points: if tally.games == 0 { 0.0 } else { tally.points / tally.games as f64 },
+            unscored_games: tally.unscored_games,
+        })
+        .collect();
+
+    // Most points first, names keep ties in a stable order
+    standings.sort_by(|a, b| b.points.total_cmp(&a.points).then(a.name.cmp(&b.name)));
+
+    for index in 0..standings.len() {
+        standings[index].position = if index > 0 && standings[index - 1].points.total_cmp(&standings[index].points) == Ordering::Equal {
+            standings[index - 1].position
+        } else {
+            index + 1
+        };
+    }
+
+    standings
+}
+
+pub async fn get_schemes(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>) -> ServerResult<Json<ScoringSchemesResponse>> {
+    let rows = store.schemes(group.id).await?;
+
+    Ok(Json(ScoringSchemesResponse {
+        schemes: rows.into_iter().map(scheme_from_row).collect::<ServerResult<_>>()?
+    }))
+}
+
+pub async fn get_scheme(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Path(IdPath { id }): Path<IdPath>) -> ServerResult<Json<ScoringSchemeResponse>> {
+    let scheme = fetch_scheme(&store, group.id, id).await?;
+
+    Ok(Json(ScoringSchemeResponse { scheme }))
+}
+
+pub async fn post_scheme(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Json(payload): Json<ScoringSchemePayload>) -> ServerResult<Json<ScoringSchemeResponse>> {
+    require_admin(&user)?;
+
+    let definition = validate_scheme(&payload)?;
+
+    let row = store.create_scheme(group.id, &payload.name, &definition).await
+        .map_err(|error| map_scheme_write_error(error, &payload.name))?;
+
+    Ok(Json(ScoringSchemeResponse { scheme: scheme_from_row(row)? }))
+}
+
+pub async fn put_scheme(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Path(IdPath { id }): Path<IdPath>, Json(payload): Json<ScoringSchemePayload>) -> ServerResult<Json<ScoringSchemeResponse>> {
+    require_admin(&user)?;
+
+    let definition = validate_scheme(&payload)?;
+
+    let row = store.update_scheme(group.id, id, &payload.name, &definition).await
+        .map_err(|error| map_scheme_write_error(error, &payload.name))?;
+
+    match row {
+        Some(row) => Ok(Json(ScoringSchemeResponse { scheme: scheme_from_row(row)? })),
+        None => Err(ApiError::SchemeNotFound { id }.into())
+    }
+}
+
+pub async fn delete_scheme(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Path(IdPath { id }): Path<IdPath>) -> ServerResult<Json<PostResponse>> {
+    require_admin(&user)?;
+
+    if !store.delete_scheme(group.id, id).await? {
+        return Err(ApiError::SchemeNotFound { id }.into());
+    }
+
+    Ok(Json(PostResponse { success: true, error: None }))
+}
+
+pub async fn get_points_leaderboard(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Path(IdPath { id }): Path<IdPath>, Query(season): Query<SeasonQuery>) -> ServerResult<Json<PointsLeaderboardResponse>> {
+    let scheme = fetch_scheme(&store, group.id, id).await?;
+    let season = resolve_season(&*store, group.id, &season).await?;
+    let games = store.games(group.id, &season_games(season.as_ref())).await?;
+
+    Ok(Json(PointsLeaderboardResponse {
+        standings: points_standings(&scheme, &games),
+        scheme,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use super::*;
+
+    fn scheme(tie_rule: TieRule) -> ScoringScheme {
+        ScoringScheme {
+            id: 1,
+            name: String::from("test"),
+            tie_rule,
+            tables: BTreeMap::from([(2, vec![3.0, 1.0]), (4, vec![4.0, 3.0, 2.0, 1.0])]),
+        }
+    }
+
+    // Players are named after their position in ranks
+    fn game(ranks: &[usize]) -> Game {
+        Game {
+            id: 1,
+            start_datetime: Utc::now(),
+            end_datetime: Utc::now(),
+            players: ranks.iter().enumerate().map(|(index, rank)| Player {
+                id: None,
+                name: format!("p{}", index + 1),
+                commanders: Vec::new(),
+                rank: *rank
+            }).collect(),
+            submitted_by: None,
+        }
+    }
+
+    fn points(scheme: &ScoringScheme, ranks: &[usize]) -> Option<Vec<f64>> {
+        game_points(scheme, &game(ranks)).map(|points| points.into_iter().map(|(_, points)| points).collect())
+    }
+
+    #[test]
+    fn untied_players_get_their_place() {
+        assert_eq!(points(&scheme(TieRule::Split), &[1, 2, 3, 4]), Some(vec![4.0, 3.0, 2.0, 1.0]));
+        assert_eq!(points(&scheme(TieRule::Highest), &[2, 1]), Some(vec![1.0, 3.0]));
+    }
+
+    #[test]
+    fn tied_players_split_or_share_the_best_place() {
+        assert_eq!(points(&scheme(TieRule::Split), &[1, 2, 2, 4]), Some(vec![4.0, 2.5, 2.5, 1.0]));
+        assert_eq!(points(&scheme(TieRule::Highest), &[1, 2, 2, 4]), Some(vec![4.0, 3.0, 3.0, 1.0]));
+
+        assert_eq!(points(&scheme(TieRule::Split), &[1, 1]), Some(vec![2.0, 2.0]));
+        assert_eq!(points(&scheme(TieRule::Highest), &[1, 1, 1, 1]), Some(vec![4.0, 4.0, 4.0, 4.0]));
+    }
+
+    #[test]
+    fn pods_without_a_table_are_unscored() {
+        assert_eq!(points(&scheme(TieRule::Split), &[1, 2, 3]), None);
+        assert_eq!(points(&scheme(TieRule::Split), &[]), None);
+
+        let standings = points_standings(&scheme(TieRule::Split), &[game(&[1, 2, 3]), game(&[2, 1])]);
+        assert_eq!(standings[0].name, "p2");
+        assert_eq!((standings[0].points, standings[0].games, standings[0].unscored_games), (3.0, 1, 1));
+        assert_eq!(standings[1].name, "p1");
+        assert_eq!((standings[1].points, standings[1].games, standings[1].unscored_games), (1.0, 1, 1));
+        assert_eq!(standings[2].name, "p3");
+        assert_eq!((standings[2].points, standings[2].games, standings[2].unscored_games), (0.0, 0, 1));
+    }
+
+    #[test]
+    fn players_on_the_same_points_share_a_position() {
+        let standings = points_standings(&scheme(TieRule::Split), &[game(&[1, 2]), game(&[2, 1]), game(&[1, 2, 3, 4])]);
+        let positions: Vec<(&str, usize)> = standings.iter().map(|standing| (standing.name.as_str(), standing.position)).collect();
+        assert_eq!(positions, [("p1", 1), ("p2", 2), ("p3", 3), ("p4", 4)]);
+
+        let standings = points_standings(&scheme(TieRule::Split), &[game(&[1, 2]), game(&[2, 1])]);
+        let positions: Vec<(&str, usize)> = standings.iter().map(|standing| (standing.name.as_str(), standing.position)).collect();
+        assert_eq!(positions, [("p1", 1), ("p2", 1)]);
+    }
+}
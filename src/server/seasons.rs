@@ -0,0 +1,242 @@
+use axum::Extension;
+use std::{cmp::Ordering, collections::{BTreeMap, HashMap}};
+use ormos::messages::*;
+use crate::{IdPath, accounts::{CurrentUser, require_admin}, error::{ServerError, ServerResult}, extract::{Json, Path, Query}, playgroups::CurrentPlaygroup, ratings, stats::Record, store::{SharedStore, Store, StoreError}};
+
+// Turns the season query parameter into the season itself, or a 404 if there's no such season
+pub async fn resolve_season(store: &dyn Store, playgroup_id: i32, query: &SeasonQuery) -> ServerResult<Option<Season>> {
+    let Some(id) = query.season else {
+        return Ok(None);
+    };
+
+    match store.season(playgroup_id, id).await? {
+        Some(season) => Ok(Some(season)),
+        None => Err(ApiError::SeasonNotFound { id }.into())
+    }
+}
+
+// Games limited to the season, or every game without one
+pub fn season_games(season: Option<&Season>) -> GamesQuery {
+    GamesQuery {
+        from: season.map(|season| season.start_datetime),
+        to: season.map(|season| season.end_datetime),
+        ..Default::default()
+    }
+}
+
+fn validate_season(payload: &SeasonPayload) -> ServerResult<()> {
+    if payload.name.trim().is_empty() {
+        return Err(ApiError::MissingField { field: String::from("name") }.into());
+    }
+
+    if payload.end_datetime <= payload.start_datetime {
+        return Err(ApiError::InvalidDateRange.into());
+    }
+
+    Ok(())
+}
+
+fn map_season_write_error(error: StoreError, name: &str) -> ServerError {
+    match error {
+        StoreError::Duplicate => ServerError(ApiError::SeasonExists { name: name.to_string() }),
+        _ => ServerError::from(error)
+    }
+}
+
+pub async fn get_seasons(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>) -> ServerResult<Json<SeasonsResponse>> {
+    Ok(Json(SeasonsResponse {
+        seasons: store.seasons(group.id).await?
+    }))
+}
+
+pub async fn get_season(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Path(IdPath { id }): Path<IdPath>) -> ServerResult<Json<SeasonResponse>> {
+    let season = resolve_season(&*store, group.id, &SeasonQuery { season: Some(id) }).await?.ok_or(ApiError::SeasonNotFound { id })?;
+
+    Ok(Json(SeasonResponse { season }))
+}
+
+pub async fn post_season(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Json(payload): Json<SeasonPayload>) -> ServerResult<Json<SeasonResponse>> {
+    require_admin(&user)?;
+
+    validate_season(&payload)?;
+
+    let season = store.create_season(group.id, &payload).await
+        .map_err(|error| map_season_write_error(error, &payload.name))?;
+
+    Ok(Json(SeasonResponse { season }))
+}
+
+pub async fn put_season(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Path(IdPath { id }): Path<IdPath>, Json(payload): Json<SeasonPayload>) -> ServerResult<Json<SeasonResponse>> {
+    require_admin(&user)?;
+
+    validate_season(&payload)?;
+
+    let season = store.update_season(group.id, id, &payload).await
+        .map_err(|error| map_season_write_error(error, &payload.name))?;
+
+    match season {
+        Some(season) => Ok(Json(SeasonResponse { season })),
+        None => Err(ApiError::SeasonNotFound { id }.into())
+    }
+}
+
+// Seasons are just a name for a date range, deleting one doesn't touch any games
+pub async fn delete_season(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Extension(user): Extension<CurrentUser>, Path(IdPath { id }): Path<IdPath>) -> ServerResult<Json<PostResponse>> {
+    require_admin(&user)?;
+
+    if !store.delete_season(group.id, id).await? {
+        return Err(ApiError::SeasonNotFound { id }.into());
+    }
+
+    Ok(Json(PostResponse { success: true, error: None }))
+}
+
+fn compare_standings(sort: StandingsSort, a: &Standing, b: &Standing) -> Ordering {
+    let games = b.games.cmp(&a.games);
+    let wins = b.wins.cmp(&a.wins);
+    let win_rate = b.win_rate.total_cmp(&a.win_rate);
+    let average_rank = a.average_rank.total_cmp(&b.average_rank);
+    let rating = b.rating.total_cmp(&a.rating);
+
+    let primary = match sort {
+        StandingsSort::Wins => wins,
+        StandingsSort::WinRate => win_rate,
+        StandingsSort::AverageRank => average_rank,
+        StandingsSort::Games => games,
+        StandingsSort::Rating => rating,
+    };
+
+    primary
+        .then(wins)
+        .then(win_rate)
+        .then(average_rank)
+        .then(games)
+        .then(rating)
+}
+
+pub fn standings(games: &[Game], query: &StandingsQuery) -> Vec<Standing> {
+    let mut records = BTreeMap::<String, Record>::new();
+    for game in games {
+        for player in game.players.iter() {
+            records.entry(player.name.clone()).or_default().add(player.rank);
+        }
+    }
+
+    // Ratings start over every season, so they come from only this season's games
+    let mut season_ratings = HashMap::<String, f64>::new();
+    for entry in ratings::leaderboard(ratings::rating_rows(games, &ratings::compute_ratings(games))) {
+        season_ratings.insert(entry.name, entry.rating);
+    }
+
+    let mut standings: Vec<Standing> = records
+        .into_iter()
+        .filter(|(_, record)| record.games >= query.min_games.unwrap_or(0))
+        .map(|(name, record)| Standing {
+            position: 0,
+            games: record.games,
+            wins: record.wins,
+            win_rate: record.win_rate(),
+            average_rank: record.average_rank().unwrap_or_default(),
+            rating: season_ratings.get(&name).copied().unwrap_or(ratings::INITIAL_RATING),
+            name,
+        })
+        .collect();
+
+    standings.sort_by(|a, b| compare_standings(query.sort, a, b).then(a.name.cmp(&b.name)));
+
+    // Standard competition ranking, two players tied for first are both
+    // first and the next player is third
+    for index in 0..standings.len() {
+        standings[index].position = if index > 0 && compare_standings(query.sort, &standings[index - 1], &standings[index]) == Ordering::Equal {
+            standings[index - 1].position
+        } else {
+            index + 1
+        };
+    }
+
+    standings
+}
+
+pub async fn get_season_leaderboard(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Path(IdPath { id }): Path<IdPath>, Query(query): Query<StandingsQuery>) -> ServerResult<Json<SeasonLeaderboardResponse>> {
+    let season = resolve_season(&*store, group.id, &SeasonQuery { season: Some(id) }).await?.ok_or(ApiError::SeasonNotFound { id })?;
+    let games = store.games(group.id, &season_games(Some(&season))).await?;
+
+    Ok(Json(SeasonLeaderboardResponse {
+        standings: standings(&games, &query),
+        season,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use crate::store::{MemoryStore, NewGame};
+    use super::*;
+
+    fn at(month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, month, day, hour, 0, 0).unwrap()
+    }
+
+    fn player(name: &str, rank: usize) -> Player {
+        Player { id: None, name: name.to_string(), commanders: vec![String::from("Krenko, Mob Boss")], rank }
+    }
+
+    fn game(players: &[(&str, usize)]) -> Game {
+        Game {
+            id: 1,
+            start_datetime: at(1, 1, 0),
+            end_datetime: at(1, 1, 1),
+            players: players.iter().map(|(name, rank)| player(name, *rank)).collect(),
+            submitted_by: None,
+        }
+    }
+
+    #[test]
+    fn no_games_means_no_standings() {
+        assert!(standings(&[], &StandingsQuery::default()).is_empty());
+    }
+
+    #[test]
+    fn tied_players_share_a_position_and_skip_the_next() {
+        let games = [game(&[("alice", 1), ("bob", 1), ("carol", 3)])];
+        let standings = standings(&games, &StandingsQuery::default());
+
+        let positions: Vec<(&str, usize)> = standings.iter().map(|standing| (standing.name.as_str(), standing.position)).collect();
+        assert_eq!(positions, [("alice", 1), ("bob", 1), ("carol", 3)]);
+        assert_eq!(standings[0].wins, 1);
+        assert_eq!(standings[2].average_rank, 3.0);
+    }
+
+    #[test]
+    fn players_below_min_games_are_left_off() {
+        let games = [game(&[("alice", 1), ("bob", 2)]), game(&[("alice", 2), ("carol", 1)])];
+        let standings = standings(&games, &StandingsQuery { min_games: Some(2), ..StandingsQuery::default() });
+
+        let names: Vec<&str> = standings.iter().map(|standing| standing.name.as_str()).collect();
+        assert_eq!(names, ["alice"]);
+    }
+
+    // A season covers games starting at its start and up to but not including its end
+    #[tokio::test]
+    async fn season_games_stop_short_of_the_end() {
+        let store = MemoryStore::new();
+        let playgroup_id = 1;
+        for name in ["alice", "bob"] {
+            store.create_player(playgroup_id, 0, name).await.unwrap();
+        }
+        for start in [at(1, 1, 0) - Duration::seconds(1), at(1, 1, 0), at(1, 31, 23), at(2, 1, 0)] {
+            store.create_game(playgroup_id, 0, NewGame {
+                start_datetime: start,
+                end_datetime: start + Duration::hours(1),
+                players: vec![player("alice", 1), player("bob", 2)]
+            }).await.unwrap();
+        }
+
+        let season = Season { id: 1, name: String::from("January"), start_datetime: at(1, 1, 0), end_datetime: at(2, 1, 0) };
+        let games = store.games(playgroup_id, &season_games(Some(&season))).await.unwrap();
+
+        let starts: Vec<DateTime<Utc>> = games.iter().map(|game| game.start_datetime).collect();
+        assert_eq!(starts, [at(1, 31, 23), at(1, 1, 0)]);
+        assert_eq!(store.games(playgroup_id, &season_games(None)).await.unwrap().len(), 4);
+    }
+}
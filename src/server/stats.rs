@@ -0,0 +1,579 @@
+use axum::Extension;
+use std::{cmp::{Ordering, Reverse}, collections::{BTreeMap, BTreeSet, HashMap}};
+use chrono::{DateTime, Utc};
+use ormos::messages::*;
+use crate::{NamePath, error::ServerResult, extract::{Json, Path, Query}, playgroups::CurrentPlaygroup, ratings::fetch_rating_history, seasons::{resolve_season, season_games}, store::SharedStore};
+
+// How many commanders the stats endpoints list at most
+const TOP_COMMANDERS: usize = 5;
+// Fewer games than this and a deck's win rate says more about luck than the deck
+const MIN_PERFORMANCE_GAMES: usize = 3;
+
+// Running totals for anything we report wins and placements for
+#[derive(Default, Clone)]
+pub struct Record {
+    pub games: usize,
+    pub wins: usize,
+    pub rank_total: usize,
+}
+
+impl Record {
+    pub fn add(&mut self, rank: usize) {
+        self.games += 1;
+        self.rank_total += rank;
+        // Shared first place still counts as a win
+        if rank == 1 {
+            self.wins += 1;
+        }
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        self.wins as f64 / self.games as f64
+    }
+
+    pub fn average_rank(&self) -> Option<f64> {
+        if self.games == 0 {
+            return None;
+        }
+        Some(self.rank_total as f64 / self.games as f64)
+    }
+}
+
+// Partners are stored as separate rows, sorting them means
+// "Thrasios + Tymna" and "Tymna + Thrasios" are the same deck
+pub fn deck(player: &Player) -> Vec<String> {
+    let mut commanders = player.commanders.clone();
+    commanders.sort();
+    commanders
+}
+
+fn commander_record(commanders: Vec<String>, record: &Record) -> CommanderRecord {
+    CommanderRecord {
+        commanders,
+        games: record.games,
+        wins: record.wins,
+        win_rate: record.win_rate(),
+        average_rank: record.average_rank().unwrap_or_default(),
+    }
+}
+
+// Decks with enough games first, so 1 win from 1 game doesn't beat 30 from 40,
+// then higher win rate, then better average placement, then more games
+fn compare_performance(a: &CommanderRecord, b: &CommanderRecord) -> Ordering {
+    (b.games >= MIN_PERFORMANCE_GAMES).cmp(&(a.games >= MIN_PERFORMANCE_GAMES))
+        .then(b.win_rate.total_cmp(&a.win_rate))
+        .then(a.average_rank.total_cmp(&b.average_rank))
+        .then(b.games.cmp(&a.games))
+        .then(a.commanders.cmp(&b.commanders))
+}
+
+pub fn player_stats(name: &str, games: &[Game]) -> PlayerStatsResponse {
+    let mut overall = Record::default();
+    let mut by_pod_size = BTreeMap::<usize, Record>::new();
+    let mut by_deck = BTreeMap::<Vec<String>, Record>::new();
+    let mut first_game = None;
+    let mut last_game = None;
+
+    for game in games {
+        let Some(player) = game.players.iter().find(|player| player.name == name) else {
+            continue
+        };
+
+        overall.add(player.rank);
+        by_pod_size.entry(game.players.len()).or_default().add(player.rank);
+        by_deck.entry(deck(player)).or_default().add(player.rank);
+
+        first_game = Some(first_game.map_or(game.start_datetime, |first: DateTime<Utc>| first.min(game.start_datetime)));
+        last_game = Some(last_game.map_or(game.start_datetime, |last: DateTime<Utc>| last.max(game.start_datetime)));
+    }
+
+    let decks: Vec<CommanderRecord> = by_deck
+        .into_iter()
+        .map(|(commanders, record)| commander_record(commanders, &record))
+        .collect();
+
+    let mut most_played_commanders = decks.clone();
+    most_played_commanders.sort_by(|a, b| b.games.cmp(&a.games).then(compare_performance(a, b)));
+    most_played_commanders.truncate(TOP_COMMANDERS);
+
+    let mut best_commanders = decks;
+    best_commanders.sort_by(compare_performance);
+    best_commanders.truncate(TOP_COMMANDERS);
+
+    PlayerStatsResponse {
+        name: name.to_string(),
+        games: overall.games,
+        wins: overall.wins,
+        win_rate: overall.win_rate(),
+        average_rank: overall.average_rank(),
+        first_game,
+        last_game,
+        by_pod_size: by_pod_size
+            .into_iter()
+            .map(|(players, record)| PodSizeStats {
+                players,
+                games: record.games,
+                wins: record.wins,
+                win_rate: record.win_rate(),
+            })
+            .collect(),
+        most_played_commanders,
+        best_commanders,
+    }
+}
+
+pub async fn get_player_stats(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Path(NamePath { name }): Path<NamePath>, Query(season): Query<SeasonQuery>) -> ServerResult<Json<PlayerStatsResponse>> {
+    if !store.player_exists(group.id, &name).await? {
+        return Err(ApiError::PlayerNotFound { player: name.clone() }.into());
+    }
+
+    let season = resolve_season(&*store, group.id, &season).await?;
+    let query = GamesQuery {
+        player: Some(name.clone()),
+        ..season_games(season.as_ref())
+    };
+    let games = store.games(group.id, &query).await?;
+
+    Ok(Json(player_stats(&name, &games)))
+}
+
+#[derive(Default)]
+struct CommanderTally {
+    record: Record,
+    pilots: BTreeSet<String>,
+}
+
+impl CommanderTally {
+    fn to_stats(&self, commanders: Vec<String>) -> CommanderStats {
+        CommanderStats {
+            commanders,
+            games: self.record.games,
+            wins: self.record.wins,
+            win_rate: self.record.win_rate(),
+            average_rank: self.record.average_rank().unwrap_or_default(),
+            pilots: self.pilots.len(),
+        }
+    }
+}
+
+// A partner pair counts as the pair and as each of its commanders
+fn commander_entries(player: &Player) -> Vec<Vec<String>> {
+    let mut commanders = deck(player);
+    commanders.dedup();
+
+    let mut entries = vec![commanders.clone()];
+    if commanders.len() > 1 {
+        entries.extend(commanders.into_iter().map(|commander| vec![commander]));
+    }
+    entries
+}
+
+fn tally_commanders(games: &[Game]) -> BTreeMap<Vec<String>, CommanderTally> {
+    let mut tallies = BTreeMap::<Vec<String>, CommanderTally>::new();
+
+    for game in games {
+        for player in game.players.iter() {
+            for entry in commander_entries(player) {
+                let tally = tallies.entry(entry).or_default();
+                tally.record.add(player.rank);
+                tally.pilots.insert(player.name.clone());
+            }
+        }
+    }
+
+    tallies
+}
+
+fn compare_commanders(sort: CommanderSort, a: &CommanderStats, b: &CommanderStats) -> Ordering {
+    match sort {
+        CommanderSort::Games => a.games.cmp(&b.games),
+        CommanderSort::Wins => a.wins.cmp(&b.wins),
+        CommanderSort::WinRate => a.win_rate.total_cmp(&b.win_rate),
+        CommanderSort::AverageRank => a.average_rank.total_cmp(&b.average_rank),
+        CommanderSort::Pilots => a.pilots.cmp(&b.pilots),
+        CommanderSort::Name => a.commanders.cmp(&b.commanders),
+    }
+}
+
+pub fn commanders_stats(games: &[Game], query: &CommandersStatsQuery) -> Vec<CommanderStats> {
+    let mut commanders: Vec<CommanderStats> = tally_commanders(games)
+        .into_iter()
+        .map(|(commanders, tally)| tally.to_stats(commanders))
+        .filter(|stats| stats.games >= query.min_games.unwrap_or(0))
+        .collect();
+
+    let order = query.order.unwrap_or(match query.sort {
+        CommanderSort::AverageRank | CommanderSort::Name => SortOrder::Asc,
+        _ => SortOrder::Desc,
+    });
+
+    // The tallies come out sorted by name, and sort_by is stable,
+    // so ties always end up in name order
+    commanders.sort_by(|a, b| match order {
+        SortOrder::Asc => compare_commanders(query.sort, a, b),
+        SortOrder::Desc => compare_commanders(query.sort, b, a),
+    });
+
+    if let Some(limit) = query.limit {
+        commanders.truncate(limit);
+    }
+
+    commanders
+}
+
+// Returns None if the commander hasn't been in any of the games
+pub fn commander_stats(name: &str, games: &[Game]) -> Option<CommanderStatsResponse> {
+    let tallies = tally_commanders(games);
+    let stats = tallies.get(&vec![name.to_string()])?.to_stats(vec![name.to_string()]);
+
+    let partners = tallies
+        .iter()
+        .filter(|(commanders, _)| commanders.len() > 1 && commanders.iter().any(|commander| commander == name))
+        .map(|(commanders, tally)| tally.to_stats(commanders.clone()))
+        .collect();
+
+    let mut opponents = BTreeMap::<Vec<String>, OpponentRecord>::new();
+
+    for game in games {
+        for player in game.players.iter().filter(|player| player.commanders.iter().any(|commander| commander == name)) {
+            for opponent in game.players.iter().filter(|opponent| opponent.name != player.name) {
+                let commanders = deck(opponent);
+                let record = opponents.entry(commanders.clone()).or_insert(OpponentRecord {
+                    commanders,
+                    games: 0,
+                    finished_ahead: 0
+                });
+                record.games += 1;
+                if player.rank < opponent.rank {
+                    record.finished_ahead += 1;
+                }
+            }
+        }
+    }
+
+    let mut most_played_against: Vec<OpponentRecord> = opponents.into_values().collect();
+    most_played_against.sort_by_key(|record| Reverse(record.games));
+    most_played_against.truncate(TOP_COMMANDERS);
+
+    Some(CommanderStatsResponse {
+        stats,
+        partners,
+        most_played_against,
+    })
+}
+
+pub async fn get_commander_stats(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Path(NamePath { name }): Path<NamePath>, Query(season): Query<SeasonQuery>) -> ServerResult<Json<CommanderStatsResponse>> {
+    let season = resolve_season(&*store, group.id, &season).await?;
+    let query = GamesQuery {
+        commander: Some(name.clone()),
+        ..season_games(season.as_ref())
+    };
+    let games = store.games(group.id, &query).await?;
+
+    match commander_stats(&name, &games) {
+        Some(stats) => Ok(Json(stats)),
+        None => Err(ApiError::CommanderNotFound { commander: name }.into())
+    }
+}
+
+pub async fn get_commanders_stats(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Query(query): Query<CommandersStatsQuery>, Query(season): Query<SeasonQuery>) -> ServerResult<Json<CommandersStatsResponse>> {
+    let season = resolve_season(&*store, group.id, &season).await?;
+    let games = store.games(group.id, &season_games(season.as_ref())).await?;
+
+    Ok(Json(CommandersStatsResponse {
+        commanders: commanders_stats(&games, &query)
+    }))
+}
+
+// Only games where every one of names took part count
+pub fn head_to_head(names: &[String], games: &[Game]) -> HeadToHeadResponse {
+    let shared: Vec<&Game> = games
+        .iter()
+        .filter(|game| names.iter().all(|name| game.players.iter().any(|player| &player.name == name)))
+        .collect();
+
+    let rank_of = |game: &Game, name: &String| {
+        game.players.iter().find(|player| &player.name == name).map(|player| player.rank).unwrap_or_default()
+    };
+
+    let players = names
+        .iter()
+        .map(|name| {
+            let mut record = Record::default();
+            let mut best_of_group = 0;
+
+            for game in shared.iter() {
+                let rank = rank_of(game, name);
+                record.add(rank);
+                if names.iter().all(|other| rank <= rank_of(game, other)) {
+                    best_of_group += 1;
+                }
+            }
+
+            HeadToHeadPlayer {
+                name: name.clone(),
+                wins: record.wins,
+                best_of_group,
+                average_rank: record.average_rank().unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    let mut matchups = Vec::new();
+    for name in names.iter() {
+        for opponent in names.iter().filter(|opponent| *opponent != name) {
+            let mut matchup = Matchup {
+                player: name.clone(),
+                opponent: opponent.clone(),
+                ahead: 0,
+                behind: 0,
+                tied: 0,
+            };
+
+            for game in shared.iter() {
+                match rank_of(game, name).cmp(&rank_of(game, opponent)) {
+                    Ordering::Less => matchup.ahead += 1,
+                    Ordering::Greater => matchup.behind += 1,
+                    Ordering::Equal => matchup.tied += 1,
+                }
+            }
+
+            matchups.push(matchup);
+        }
+    }
+
+    let results = shared
+        .iter()
+        .map(|game| HeadToHeadGame {
+            id: game.id,
+            start_datetime: game.start_datetime,
+            winners: game.players.iter().filter(|player| player.rank == 1).map(|player| player.name.clone()).collect(),
+        })
+        .collect();
+
+    HeadToHeadResponse {
+        games: shared.len(),
+        players,
+        matchups,
+        results,
+    }
+}
+
+pub async fn get_head_to_head(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Query(query): Query<HeadToHeadQuery>, Query(season): Query<SeasonQuery>) -> ServerResult<Json<HeadToHeadResponse>> {
+    let mut names: Vec<String> = Vec::new();
+    for name in query.players.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+        if !names.iter().any(|existing| existing == name) {
+            names.push(name.to_string());
+        }
+    }
+
+    if names.len() < 2 {
+        return Err(ApiError::TooFewHeadToHeadPlayers.into());
+    }
+
+    for name in names.iter() {
+        if !store.player_exists(group.id, name).await? {
+            return Err(ApiError::PlayerNotFound { player: name.clone() }.into());
+        }
+    }
+
+    let season = resolve_season(&*store, group.id, &season).await?;
+
+    // Every shared game includes the first player, so that's enough to narrow it down in SQL
+    let query = GamesQuery {
+        player: Some(names[0].clone()),
+        ..season_games(season.as_ref())
+    };
+    let games = store.games(group.id, &query).await?;
+
+    Ok(Json(head_to_head(&names, &games)))
+}
+
+// ratings maps a game ID to the player's rating after that game
+pub fn player_history(name: &str, games: &[Game], ratings: &HashMap<i32, f64>) -> Vec<HistoryPoint> {
+    let mut games: Vec<&Game> = games.iter().collect();
+    games.sort_by_key(|game| (game.start_datetime, game.id));
+
+    let mut record = Record::default();
+    let mut history = Vec::new();
+
+    for game in games {
+        let Some(player) = game.players.iter().find(|player| player.name == name) else {
+            continue
+        };
+
+        record.add(player.rank);
+
+        history.push(HistoryPoint {
+            game_id: game.id,
+            start_datetime: game.start_datetime,
+            players: game.players.len(),
+            rank: player.rank,
+            commanders: player.commanders.clone(),
+            opponents: game.players
+                .iter()
+                .filter(|opponent| opponent.name != name)
+                .map(|opponent| opponent.name.clone())
+                .collect(),
+            win_rate: record.win_rate(),
+            average_rank: record.average_rank().unwrap_or_default(),
+            rating: ratings.get(&game.id).copied(),
+        });
+    }
+
+    history
+}
+
+pub async fn get_player_history(Extension(store): Extension<SharedStore>, Extension(group): Extension<CurrentPlaygroup>, Path(NamePath { name }): Path<NamePath>, Query(season): Query<SeasonQuery>) -> ServerResult<Json<PlayerHistoryResponse>> {
+    if !store.player_exists(group.id, &name).await? {
+        return Err(ApiError::PlayerNotFound { player: name.clone() }.into());
+    }
+
+    let season = resolve_season(&*store, group.id, &season).await?;
+    let query = GamesQuery {
+        player: Some(name.clone()),
+        ..season_games(season.as_ref())
+    };
+    let games = store.games(group.id, &query).await?;
+
+    let ratings: HashMap<i32, f64> = fetch_rating_history(&*store, group.id, season.as_ref(), &name)
+        .await?
+        .into_iter()
+        .map(|point| (point.game_id, point.rating_after))
+        .collect();
+
+    let history = player_history(&name, &games, &ratings);
+
+    Ok(Json(PlayerHistoryResponse { name, history }))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use super::*;
+
+    // Each player is (name, commanders, rank), games start a day apart in the order given
+    fn games(games: &[&[(&str, &[&str], usize)]]) -> Vec<Game> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        games
+            .iter()
+            .enumerate()
+            .map(|(index, players)| Game {
+                id: index as i32 + 1,
+                start_datetime: start + Duration::days(index as i64),
+                end_datetime: start + Duration::days(index as i64) + Duration::hours(1),
+                players: players.iter().map(|(name, commanders, rank)| Player {
+                    id: None,
+                    name: name.to_string(),
+                    commanders: commanders.iter().map(|commander| commander.to_string()).collect(),
+                    rank: *rank
+                }).collect(),
+                submitted_by: None,
+            })
+            .collect()
+    }
+
+    fn names(records: &[CommanderRecord]) -> Vec<String> {
+        records.iter().map(|record| record.commanders.join(" + ")).collect()
+    }
+
+    #[test]
+    fn player_stats_add_up() {
+        let games = games(&[
+            &[("alice", &["Krenko"], 1), ("bob", &["Edgar"], 2)],
+            &[("alice", &["Tymna", "Thrasios"], 2), ("bob", &["Edgar"], 1), ("carol", &["Atraxa"], 3)],
+            &[("alice", &["Thrasios", "Tymna"], 1), ("bob", &["Edgar"], 1), ("carol", &["Atraxa"], 3)],
+            &[("bob", &["Edgar"], 1), ("carol", &["Atraxa"], 2)],
+        ]);
+
+        let stats = player_stats("alice", &games);
+        assert_eq!((stats.games, stats.wins), (3, 2));
+        assert_eq!(stats.average_rank, Some(4.0 / 3.0));
+        assert_eq!(stats.first_game, Some(games[0].start_datetime));
+        assert_eq!(stats.last_game, Some(games[2].start_datetime));
+
+        let pod_sizes: Vec<(usize, usize, usize)> = stats.by_pod_size.iter().map(|pod| (pod.players, pod.games, pod.wins)).collect();
+        assert_eq!(pod_sizes, [(2, 1, 1), (3, 2, 1)]);
+
+        // Partners in either order are the same deck
+        assert_eq!(names(&stats.most_played_commanders), ["Thrasios + Tymna", "Krenko"]);
+
+        let stats = player_stats("dave", &games);
+        assert_eq!((stats.games, stats.average_rank, stats.first_game), (0, None, None));
+    }
+
+    #[test]
+    fn best_commanders_need_enough_games() {
+        let games = games(&[
+            &[("alice", &["Krenko"], 1), ("bob", &["Edgar"], 2)],
+            &[("alice", &["Atraxa"], 1), ("bob", &["Edgar"], 2)],
+            &[("alice", &["Atraxa"], 1), ("bob", &["Edgar"], 2)],
+            &[("alice", &["Atraxa"], 2), ("bob", &["Edgar"], 1)],
+        ]);
+
+        // 1 win from 1 game is a better rate, but 2 from 3 says more
+        let stats = player_stats("alice", &games);
+        assert_eq!(names(&stats.best_commanders), ["Atraxa", "Krenko"]);
+    }
+
+    #[test]
+    fn partners_count_as_the_pair_and_each_commander() {
+        let games = games(&[
+            &[("alice", &["Thrasios", "Tymna"], 1), ("bob", &["Tymna", "Kraum"], 2)],
+            &[("alice", &["Tymna", "Thrasios"], 2), ("carol", &["Krenko"], 1)],
+        ]);
+
+        let stats = commanders_stats(&games, &CommandersStatsQuery::default());
+        let tymna = stats.iter().find(|stats| stats.commanders == ["Tymna"]).unwrap();
+        assert_eq!((tymna.games, tymna.wins, tymna.pilots), (3, 1, 2));
+        let pair = stats.iter().find(|stats| stats.commanders == ["Thrasios", "Tymna"]).unwrap();
+        assert_eq!((pair.games, pair.wins, pair.average_rank), (2, 1, 1.5));
+
+        let stats = commanders_stats(&games, &CommandersStatsQuery { min_games: Some(2), ..CommandersStatsQuery::default() });
+        let names: Vec<String> = stats.iter().map(|stats| stats.commanders.join(" + ")).collect();
+        assert_eq!(names, ["Tymna", "Thrasios", "Thrasios + Tymna"]);
+
+        let stats = commander_stats("Tymna", &games).unwrap();
+        assert_eq!(stats.stats.games, 3);
+        assert_eq!(stats.partners.len(), 2);
+        assert!(commander_stats("Atraxa", &games).is_none());
+    }
+
+    #[test]
+    fn head_to_head_only_counts_shared_games() {
+        let games = games(&[
+            &[("alice", &["Krenko"], 1), ("bob", &["Edgar"], 2), ("carol", &["Atraxa"], 3)],
+            &[("alice", &["Krenko"], 2), ("bob", &["Edgar"], 2), ("carol", &["Atraxa"], 1)],
+            &[("alice", &["Krenko"], 1), ("carol", &["Atraxa"], 2)],
+        ]);
+
+        let result = head_to_head(&[String::from("alice"), String::from("bob")], &games);
+        assert_eq!(result.games, 2);
+
+        let alice = &result.players[0];
+        assert_eq!((alice.wins, alice.best_of_group, alice.average_rank), (1, 2, 1.5));
+
+        let matchup = &result.matchups[0];
+        assert_eq!((matchup.player.as_str(), matchup.ahead, matchup.behind, matchup.tied), ("alice", 1, 0, 1));
+        assert_eq!(result.results[1].winners, ["carol"]);
+    }
+
+    #[test]
+    fn history_keeps_running_totals_oldest_first() {
+        let mut games = games(&[
+            &[("alice", &["Krenko"], 1), ("bob", &["Edgar"], 2)],
+            &[("alice", &["Krenko"], 2), ("bob", &["Edgar"], 1)],
+            &[("bob", &["Edgar"], 1), ("carol", &["Atraxa"], 2)],
+        ]);
+        games.reverse();
+
+        let history = player_history("alice", &games, &HashMap::from([(2, 1490.0)]));
+        let points: Vec<(i32, f64, f64, Option<f64>)> = history.iter().map(|point| (point.game_id, point.win_rate, point.average_rank, point.rating)).collect();
+        assert_eq!(points, [(1, 1.0, 1.0, None), (2, 0.5, 1.5, Some(1490.0))]);
+        assert_eq!(history[0].opponents, ["bob"]);
+    }
+}
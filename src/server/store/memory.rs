@@ -0,0 +1,696 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use std::{collections::{HashMap, HashSet}, sync::{Mutex, MutexGuard}};
+use ormos::messages::*;
+use crate::{accounts::{self, CurrentUser}, audit, playgroups::{CurrentPlaygroup, DEFAULT_SLUG}, ratings::{self, RatingRow}, scoring::SchemeRow};
+use super::{LoginRow, NewGame, Store, StoreError, merge_game};
+
+struct PlaygroupRecord {
+    id: i32,
+    slug: String,
+    name: String,
+}
+
+struct UserRecord {
+    id: i32,
+    username: String,
+    password_hash: String,
+    admin: bool,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+struct SessionRecord {
+    user_id: i32,
+    token_hash: String,
+    expires_at: DateTime<Utc>,
+}
+
+struct ApiTokenRecord {
+    user_id: i32,
+    token_hash: String,
+    token: ApiToken,
+}
+
+struct AuditRecord {
+    playgroup_id: i32,
+    actor_id: i32,
+    entry: AuditEntry,
+}
+
+struct PlayerRecord {
+    id: i32,
+    playgroup_id: i32,
+    name: String,
+}
+
+struct GameRecord {
+    playgroup_id: i32,
+    game: Game,
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+struct RatingRecord {
+    game_id: i32,
+    player_id: i32,
+    rating_before: f64,
+    rating_after: f64,
+}
+
+// Every "table" as a list, kept in insertion order like rows would be
+#[derive(Default)]
+struct State {
+    // Shared by everything, IDs only have to be unique within a table
+    next_id: i32,
+    playgroups: Vec<PlaygroupRecord>,
+    users: Vec<UserRecord>,
+    sessions: Vec<SessionRecord>,
+    members: Vec<(i32, i32)>,
+    api_tokens: Vec<ApiTokenRecord>,
+    audit_log: Vec<AuditRecord>,
+    players: Vec<PlayerRecord>,
+    games: Vec<GameRecord>,
+    ratings: Vec<RatingRecord>,
+    seasons: Vec<(i32, Season)>,
+    schemes: Vec<(i32, SchemeRow)>,
+}
+
+impl State {
+    fn next_id(&mut self) -> i32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn username(&self, user_id: i32) -> Option<String> {
+        self.users.iter().find(|user| user.id == user_id).map(|user| user.username.clone())
+    }
+
+    fn game(&self, playgroup_id: i32, id: i32) -> Option<&GameRecord> {
+        self.games.iter().find(|record| record.game.id == id && record.playgroup_id == playgroup_id)
+    }
+
+    fn live_game(&self, playgroup_id: i32, id: i32) -> Option<Game> {
+        self.game(playgroup_id, id).filter(|record| record.deleted_at.is_none()).map(|record| record.game.clone())
+    }
+
+    // Newest first, the same filters as the SQL GAME_FILTER
+    fn games(&self, playgroup_id: i32, query: &GamesQuery) -> Vec<Game> {
+
+        // Same as the SQL, a cursor that isn't a game in the playgroup matches nothing
+        let cursor = match query.before {
+            Some(before) => match self.games.iter().find(|record| record.game.id == before && record.playgroup_id == playgroup_id) {
+                Some(record) => Some((record.game.start_datetime, record.game.id)),
+                None => return Vec::new()
+            },
+            None => None
+        };
+
+        let mut games: Vec<Game> = self.games
+            .iter()
+            .filter(|record| record.playgroup_id == playgroup_id && record.deleted_at.is_none())
+            .map(|record| &record.game)
+            .filter(|game| query.from.is_none_or(|from| game.start_datetime >= from))
+            .filter(|game| query.to.is_none_or(|to| game.start_datetime < to))
+            .filter(|game| query.player.as_ref().is_none_or(|name| game.players.iter().any(|player| player.name == *name)))
+            .filter(|game| query.commander.as_ref().is_none_or(|commander| game.players.iter().any(|player| player.commanders.contains(commander))))
+            .filter(|game| query.players.is_none_or(|players| game.players.len() == players))
+            .filter(|game| cursor.is_none_or(|cursor| (game.start_datetime, game.id) < cursor))
+            .cloned()
+            .collect();
+
+        games.sort_by(|a, b| b.start_datetime.cmp(&a.start_datetime).then(b.id.cmp(&a.id)));
+        if let Some(limit) = query.limit {
+            games.truncate(limit);
+        }
+        games
+    }
+
+    // Puts player IDs on a game's players and sorts them the way the databases do
+    fn resolve_players(&self, playgroup_id: i32, players: Vec<Player>) -> Result<Vec<Player>, StoreError> {
+        let mut resolved = Vec::new();
+        for player in players {
+            let Some(record) = self.players.iter().find(|record| record.playgroup_id == playgroup_id && record.name == player.name) else {
+                return Err(StoreError::UnknownPlayer(player.name));
+            };
+            resolved.push(Player { id: Some(record.id), ..player });
+        }
+        resolved.sort_by(|a, b| a.rank.cmp(&b.rank).then(a.name.cmp(&b.name)));
+        Ok(resolved)
+    }
+
+    // See Store::rebuild_ratings, the game writes call this before they return
+    fn replay_ratings(&mut self, playgroup_id: i32, from: Option<DateTime<Utc>>) {
+        let games = self.games(playgroup_id, &GamesQuery { from, ..GamesQuery::default() });
+
+        let replayed: HashSet<i32> = self.games
+            .iter()
+            .filter(|record| record.playgroup_id == playgroup_id && from.is_none_or(|from| record.game.start_datetime >= from))
+            .map(|record| record.game.id)
+            .collect();
+        self.ratings.retain(|rating| !replayed.contains(&rating.game_id));
+
+        // Whatever is left in the playgroup comes before from, the last rating wins
+        let mut kept: Vec<(DateTime<Utc>, i32, i32, f64)> = self.ratings
+            .iter()
+            .filter_map(|rating| {
+                let game = self.game(playgroup_id, rating.game_id)?;
+                Some((game.game.start_datetime, game.game.id, rating.player_id, rating.rating_after))
+            })
+            .collect();
+        kept.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+        let starting: HashMap<i32, f64> = kept.into_iter().map(|(_, _, player_id, rating)| (player_id, rating)).collect();
+
+        for change in ratings::continue_ratings(&games, starting) {
+            self.ratings.push(RatingRecord {
+                game_id: change.game_id,
+                player_id: change.player_id,
+                rating_before: change.rating_before,
+                rating_after: change.rating_after,
+            });
+        }
+    }
+
+    fn record(&mut self, playgroup_id: i32, actor_id: i32, entity: AuditEntity, entity_id: i32, action: AuditAction, change: audit::Change) {
+        let id = self.next_id();
+        let actor = self.username(actor_id);
+        self.audit_log.push(AuditRecord {
+            playgroup_id,
+            actor_id,
+            entry: AuditEntry {
+                id,
+                actor,
+                entity,
+                entity_id,
+                action,
+                timestamp: Utc::now(),
+                before: change.before,
+                after: change.after,
+            }
+        });
+    }
+}
+
+// Keeps everything in memory and forgets it on restart. Used by the
+// tests and handy for trying the server out without a database.
+pub struct MemoryStore {
+    state: Mutex<State>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        let mut state = State::default();
+        let id = state.next_id();
+        state.playgroups.push(PlaygroupRecord {
+            id,
+            slug: String::from(DEFAULT_SLUG),
+            name: String::from("Default"),
+        });
+
+        MemoryStore { state: Mutex::new(state) }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("Memory store lock was poisoned")
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn playgroup(&self, slug: &str) -> Result<Option<CurrentPlaygroup>, StoreError> {
+        Ok(self.state().playgroups
+            .iter()
+            .find(|group| group.slug == slug)
+            .map(|group| CurrentPlaygroup { id: group.id, slug: group.slug.clone() }))
+    }
+
+    async fn playgroups(&self) -> Result<Vec<Playgroup>, StoreError> {
+        let mut groups: Vec<Playgroup> = self.state().playgroups
+            .iter()
+            .map(|group| Playgroup { slug: group.slug.clone(), name: group.name.clone() })
+            .collect();
+        groups.sort_by(|a, b| a.slug.cmp(&b.slug));
+        Ok(groups)
+    }
+
+    async fn playgroup_ids(&self) -> Result<Vec<i32>, StoreError> {
+        Ok(self.state().playgroups.iter().map(|group| group.id).collect())
+    }
+
+    async fn create_playgroup(&self, slug: &str, name: &str) -> Result<(), StoreError> {
+        let mut state = self.state();
+        if state.playgroups.iter().any(|group| group.slug == slug) {
+            return Err(StoreError::Duplicate);
+        }
+        let id = state.next_id();
+        state.playgroups.push(PlaygroupRecord { id, slug: slug.to_string(), name: name.to_string() });
+        Ok(())
+    }
+
+    async fn user_count(&self) -> Result<i64, StoreError> {
+        Ok(self.state().users.len() as i64)
+    }
+
+    async fn user_id(&self, username: &str) -> Result<Option<i32>, StoreError> {
+        Ok(self.state().users.iter().find(|user| user.username == username).map(|user| user.id))
+    }
+
+    async fn login_user(&self, username: &str) -> Result<Option<LoginRow>, StoreError> {
+        Ok(self.state().users
+            .iter()
+            .find(|user| user.username == username && user.revoked_at.is_none())
+            .map(|user| (user.id, user.username.clone(), user.password_hash.clone(), user.admin)))
+    }
+
+    async fn users(&self) -> Result<Vec<User>, StoreError> {
+        let mut users: Vec<User> = self.state().users
+            .iter()
+            .map(|user| User { username: user.username.clone(), admin: user.admin, revoked: user.revoked_at.is_some() })
+            .collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        Ok(users)
+    }
+
+    async fn create_user(&self, username: &str, password_hash: &str, admin: bool) -> Result<(), StoreError> {
+        let mut state = self.state();
+        if state.users.iter().any(|user| user.username == username) {
+            return Err(StoreError::Duplicate);
+        }
+        let id = state.next_id();
+        state.users.push(UserRecord {
+            id,
+            username: username.to_string(),
+            password_hash: password_hash.to_string(),
+            admin,
+            revoked_at: None,
+        });
+        Ok(())
+    }
+
+    async fn revoke_user(&self, user_id: i32) -> Result<(), StoreError> {
+        let mut state = self.state();
+        if let Some(user) = state.users.iter_mut().find(|user| user.id == user_id && user.revoked_at.is_none()) {
+            user.revoked_at = Some(Utc::now());
+        }
+        state.sessions.retain(|session| session.user_id != user_id);
+        Ok(())
+    }
+
+    async fn create_session(&self, user_id: i32, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), StoreError> {
+        let mut state = self.state();
+        if state.sessions.iter().any(|session| session.token_hash == token_hash) {
+            return Err(StoreError::Duplicate);
+        }
+        state.sessions.push(SessionRecord { user_id, token_hash: token_hash.to_string(), expires_at });
+        Ok(())
+    }
+
+    async fn session_user(&self, token_hash: &str) -> Result<Option<CurrentUser>, StoreError> {
+        let state = self.state();
+        let now = Utc::now();
+
+        Ok(state.sessions
+            .iter()
+            .find(|session| session.token_hash == token_hash && session.expires_at > now)
+            .and_then(|session| state.users.iter().find(|user| user.id == session.user_id && user.revoked_at.is_none()))
+            .map(|user| CurrentUser { id: user.id, username: user.username.clone(), admin: user.admin, scopes: accounts::allowed_scopes(user.admin), api_token: false }))
+    }
+
+    async fn delete_session(&self, token_hash: &str) -> Result<(), StoreError> {
+        self.state().sessions.retain(|session| session.token_hash != token_hash);
+        Ok(())
+    }
+
+    async fn is_member(&self, playgroup_id: i32, user_id: i32) -> Result<bool, StoreError> {
+        Ok(self.state().members.contains(&(playgroup_id, user_id)))
+    }
+
+    async fn members(&self, playgroup_id: i32) -> Result<Vec<String>, StoreError> {
+        let state = self.state();
+        let mut members: Vec<String> = state.members
+            .iter()
+            .filter(|(group_id, _)| *group_id == playgroup_id)
+            .filter_map(|(_, user_id)| state.username(*user_id))
+            .collect();
+        members.sort();
+        Ok(members)
+    }
+
+    async fn add_member(&self, playgroup_id: i32, user_id: i32) -> Result<(), StoreError> {
+        let mut state = self.state();
+        if !state.members.contains(&(playgroup_id, user_id)) {
+            state.members.push((playgroup_id, user_id));
+        }
+        Ok(())
+    }
+
+    async fn remove_member(&self, playgroup_id: i32, user_id: i32) -> Result<bool, StoreError> {
+        let mut state = self.state();
+        let before = state.members.len();
+        state.members.retain(|member| *member != (playgroup_id, user_id));
+        Ok(state.members.len() < before)
+    }
+
+    async fn api_token_user(&self, token_hash: &str) -> Result<Option<CurrentUser>, StoreError> {
+        let state = self.state();
+        let now = Utc::now();
+
+        let Some(record) = state.api_tokens.iter().find(|record| {
+            record.token_hash == token_hash
+                && !record.token.revoked
+                && record.token.expires_at.is_none_or(|expires_at| expires_at > now)
+        }) else {
+            return Ok(None);
+        };
+
+        Ok(state.users
+            .iter()
+            .find(|user| user.id == record.user_id && user.revoked_at.is_none())
+            .map(|user| CurrentUser { id: user.id, username: user.username.clone(), admin: user.admin, scopes: record.token.scopes.clone(), api_token: true }))
+    }
+
+    async fn api_tokens(&self, user_id: i32) -> Result<Vec<ApiToken>, StoreError> {
+        let mut tokens: Vec<ApiToken> = self.state().api_tokens
+            .iter()
+            .filter(|record| record.user_id == user_id)
+            .map(|record| record.token.clone())
+            .collect();
+        tokens.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        Ok(tokens)
+    }
+
+    async fn create_api_token(&self, user_id: i32, name: &str, token_hash: &str, scopes: &[Scope], expires_at: Option<DateTime<Utc>>) -> Result<ApiToken, StoreError> {
+        let mut state = self.state();
+        if state.api_tokens.iter().any(|record| record.token_hash == token_hash) {
+            return Err(StoreError::Duplicate);
+        }
+
+        let token = ApiToken {
+            id: state.next_id(),
+            name: name.to_string(),
+            scopes: scopes.to_vec(),
+            created_at: Utc::now(),
+            expires_at,
+            revoked: false,
+        };
+        state.api_tokens.push(ApiTokenRecord { user_id, token_hash: token_hash.to_string(), token: token.clone() });
+        Ok(token)
+    }
+
+    async fn revoke_api_token(&self, id: i32, user_id: i32, any_user: bool) -> Result<bool, StoreError> {
+        let mut state = self.state();
+        match state.api_tokens.iter_mut().find(|record| record.token.id == id && (record.user_id == user_id || any_user) && !record.token.revoked) {
+            Some(record) => {
+                record.token.revoked = true;
+                Ok(true)
+            },
+            None => Ok(false)
+        }
+    }
+
+    async fn audit_entries(&self, playgroup_id: i32, query: &AuditQuery, limit: usize) -> Result<Vec<AuditEntry>, StoreError> {
+        let state = self.state();
+        Ok(state.audit_log
+            .iter()
+            .rev()
+            .filter(|record| record.playgroup_id == playgroup_id)
+            .filter(|record| query.entity.is_none_or(|entity| entity == record.entry.entity))
+            .filter(|record| query.entity_id.is_none_or(|entity_id| entity_id == record.entry.entity_id))
+            .filter(|record| query.action.is_none_or(|action| action == record.entry.action))
+            .filter(|record| query.actor.as_ref().is_none_or(|actor| state.username(record.actor_id).as_ref() == Some(actor)))
+            .filter(|record| query.from.is_none_or(|from| record.entry.timestamp >= from))
+            .filter(|record| query.to.is_none_or(|to| record.entry.timestamp < to))
+            .filter(|record| query.before.is_none_or(|before| record.entry.id < before))
+            .take(limit)
+            .map(|record| record.entry.clone())
+            .collect())
+    }
+
+    async fn players(&self, playgroup_id: i32) -> Result<Vec<String>, StoreError> {
+        Ok(self.state().players
+            .iter()
+            .filter(|player| player.playgroup_id == playgroup_id)
+            .map(|player| player.name.clone())
+            .collect())
+    }
+
+    async fn player_exists(&self, playgroup_id: i32, name: &str) -> Result<bool, StoreError> {
+        Ok(self.state().players.iter().any(|player| player.playgroup_id == playgroup_id && player.name == name))
+    }
+
+    async fn create_player(&self, playgroup_id: i32, actor_id: i32, name: &str) -> Result<(), StoreError> {
+        let mut state = self.state();
+        if state.players.iter().any(|player| player.playgroup_id == playgroup_id && player.name == name) {
+            return Err(StoreError::Duplicate);
+        }
+
+        let id = state.next_id();
+        state.players.push(PlayerRecord { id, playgroup_id, name: name.to_string() });
+        state.record(playgroup_id, actor_id, AuditEntity::Player, id, AuditAction::Create, audit::Change {
+            before: None,
+            after: Some(serde_json::json!({ "id": id, "name": name }))
+        });
+        Ok(())
+    }
+
+    async fn games(&self, playgroup_id: i32, query: &GamesQuery) -> Result<Vec<Game>, StoreError> {
+        Ok(self.state().games(playgroup_id, query))
+    }
+
+    async fn game(&self, playgroup_id: i32, id: i32) -> Result<Option<Game>, StoreError> {
+        Ok(self.state().live_game(playgroup_id, id))
+    }
+
+    async fn create_game(&self, playgroup_id: i32, actor_id: i32, game: NewGame) -> Result<Game, StoreError> {
+        let mut state = self.state();
+
+        let players = state.resolve_players(playgroup_id, game.players)?;
+        let created = Game {
+            id: state.next_id(),
+            start_datetime: game.start_datetime,
+            end_datetime: game.end_datetime,
+            players,
+            submitted_by: state.username(actor_id),
+        };
+
+        state.games.push(GameRecord { playgroup_id, game: created.clone(), deleted_at: None });
+        state.record(playgroup_id, actor_id, AuditEntity::Game, created.id, AuditAction::Create, audit::Change {
+            before: None,
+            after: audit::snapshot(&Some(&created))
+        });
+        state.replay_ratings(playgroup_id, Some(created.start_datetime));
+        Ok(created)
+    }
+
+    async fn update_game(&self, playgroup_id: i32, actor_id: i32, id: i32, changes: UpdateGamePayload) -> Result<Option<Game>, StoreError> {
+        let mut state = self.state();
+
+        let Some(existing) = state.live_game(playgroup_id, id) else {
+            return Ok(None);
+        };
+        let game = merge_game(&existing, changes)?;
+
+        let updated = Game {
+            start_datetime: game.start_datetime,
+            end_datetime: game.end_datetime,
+            players: state.resolve_players(playgroup_id, game.players)?,
+            ..existing.clone()
+        };
+
+        if let Some(record) = state.games.iter_mut().find(|record| record.game.id == id) {
+            record.game = updated.clone();
+        }
+        // Moving a game changes the ratings from wherever it was or now is, whichever is first
+        let from = existing.start_datetime.min(updated.start_datetime);
+        state.record(playgroup_id, actor_id, AuditEntity::Game, id, AuditAction::Update, audit::Change {
+            before: audit::snapshot(&Some(existing)),
+            after: audit::snapshot(&Some(&updated))
+        });
+        state.replay_ratings(playgroup_id, Some(from));
+        Ok(Some(updated))
+    }
+
+    async fn delete_game(&self, playgroup_id: i32, actor_id: i32, id: i32) -> Result<Option<Game>, StoreError> {
+        let mut state = self.state();
+
+        let Some(existing) = state.live_game(playgroup_id, id) else {
+            return Ok(None);
+        };
+
+        if let Some(record) = state.games.iter_mut().find(|record| record.game.id == id) {
+            record.deleted_at = Some(Utc::now());
+        }
+        let from = existing.start_datetime;
+        let deleted = Some(existing);
+        state.record(playgroup_id, actor_id, AuditEntity::Game, id, AuditAction::Delete, audit::Change {
+            before: audit::snapshot(&deleted),
+            after: None
+        });
+        state.replay_ratings(playgroup_id, Some(from));
+        Ok(deleted)
+    }
+
+    async fn restore_game(&self, playgroup_id: i32, actor_id: i32, id: i32) -> Result<Option<Game>, StoreError> {
+        let mut state = self.state();
+
+        let Some(record) = state.games.iter_mut().find(|record| record.game.id == id && record.playgroup_id == playgroup_id && record.deleted_at.is_some()) else {
+            return Ok(None);
+        };
+        record.deleted_at = None;
+        let from = record.game.start_datetime;
+        let restored = Some(record.game.clone());
+
+        state.record(playgroup_id, actor_id, AuditEntity::Game, id, AuditAction::Restore, audit::Change {
+            before: None,
+            after: audit::snapshot(&restored)
+        });
+        state.replay_ratings(playgroup_id, Some(from));
+        Ok(restored)
+    }
+
+    async fn rebuild_ratings(&self, playgroup_id: i32, from: Option<DateTime<Utc>>) -> Result<(), StoreError> {
+        // One lock for reading and writing, like the transaction in the SQL stores
+        self.state().replay_ratings(playgroup_id, from);
+        Ok(())
+    }
+
+    async fn unrated_since(&self, playgroup_id: i32) -> Result<Option<DateTime<Utc>>, StoreError> {
+        let state = self.state();
+
+        Ok(state.games
+            .iter()
+            .filter(|record| record.playgroup_id == playgroup_id && record.deleted_at.is_none() && record.game.players.len() > 1)
+            .filter(|record| !state.ratings.iter().any(|rating| rating.game_id == record.game.id))
+            .map(|record| record.game.start_datetime)
+            .min())
+    }
+
+    async fn rating_rows(&self, playgroup_id: i32, name: Option<&str>) -> Result<Vec<RatingRow>, StoreError> {
+        let state = self.state();
+
+        let mut rows: Vec<RatingRow> = state.ratings
+            .iter()
+            .filter_map(|rating| {
+                let game = state.game(playgroup_id, rating.game_id)?;
+                let player = state.players.iter().find(|player| player.id == rating.player_id)?;
+                Some((player.name.clone(), game.game.id, game.game.start_datetime, rating.rating_before, rating.rating_after))
+            })
+            .filter(|(row_name, ..)| name.is_none_or(|name| name == row_name))
+            .collect();
+
+        rows.sort_by(|a, b| a.2.cmp(&b.2).then(a.1.cmp(&b.1)));
+        Ok(rows)
+    }
+
+    async fn seasons(&self, playgroup_id: i32) -> Result<Vec<Season>, StoreError> {
+        let mut seasons: Vec<Season> = self.state().seasons
+            .iter()
+            .filter(|(group_id, _)| *group_id == playgroup_id)
+            .map(|(_, season)| season.clone())
+            .collect();
+        seasons.sort_by(|a, b| b.start_datetime.cmp(&a.start_datetime).then(b.id.cmp(&a.id)));
+        Ok(seasons)
+    }
+
+    async fn season(&self, playgroup_id: i32, id: i32) -> Result<Option<Season>, StoreError> {
+        Ok(self.state().seasons
+            .iter()
+            .find(|(group_id, season)| *group_id == playgroup_id && season.id == id)
+            .map(|(_, season)| season.clone()))
+    }
+
+    async fn create_season(&self, playgroup_id: i32, season: &SeasonPayload) -> Result<Season, StoreError> {
+        let mut state = self.state();
+        if state.seasons.iter().any(|(group_id, existing)| *group_id == playgroup_id && existing.name == season.name) {
+            return Err(StoreError::Duplicate);
+        }
+
+        let created = Season {
+            id: state.next_id(),
+            name: season.name.clone(),
+            start_datetime: season.start_datetime,
+            end_datetime: season.end_datetime,
+        };
+        state.seasons.push((playgroup_id, created.clone()));
+        Ok(created)
+    }
+
+    async fn update_season(&self, playgroup_id: i32, id: i32, season: &SeasonPayload) -> Result<Option<Season>, StoreError> {
+        let mut state = self.state();
+        if state.seasons.iter().any(|(group_id, existing)| *group_id == playgroup_id && existing.name == season.name && existing.id != id) {
+            return Err(StoreError::Duplicate);
+        }
+
+        Ok(state.seasons
+            .iter_mut()
+            .find(|(group_id, existing)| *group_id == playgroup_id && existing.id == id)
+            .map(|(_, existing)| {
+                existing.name = season.name.clone();
+                existing.start_datetime = season.start_datetime;
+                existing.end_datetime = season.end_datetime;
+                existing.clone()
+            }))
+    }
+
+    async fn delete_season(&self, playgroup_id: i32, id: i32) -> Result<bool, StoreError> {
+        let mut state = self.state();
+        let before = state.seasons.len();
+        state.seasons.retain(|(group_id, season)| !(*group_id == playgroup_id && season.id == id));
+        Ok(state.seasons.len() < before)
+    }
+
+    async fn schemes(&self, playgroup_id: i32) -> Result<Vec<SchemeRow>, StoreError> {
+        let mut schemes: Vec<SchemeRow> = self.state().schemes
+            .iter()
+            .filter(|(group_id, _)| *group_id == playgroup_id)
+            .map(|(_, scheme)| scheme.clone())
+            .collect();
+        schemes.sort_by(|a, b| a.1.cmp(&b.1));
+        Ok(schemes)
+    }
+
+    async fn scheme(&self, playgroup_id: i32, id: i32) -> Result<Option<SchemeRow>, StoreError> {
+        Ok(self.state().schemes
+            .iter()
+            .find(|(group_id, scheme)| *group_id == playgroup_id && scheme.0 == id)
+            .map(|(_, scheme)| scheme.clone()))
+    }
+
+    async fn create_scheme(&self, playgroup_id: i32, name: &str, definition: &str) -> Result<SchemeRow, StoreError> {
+        let mut state = self.state();
+        if state.schemes.iter().any(|(group_id, scheme)| *group_id == playgroup_id && scheme.1 == name) {
+            return Err(StoreError::Duplicate);
+        }
+
+        let scheme = (state.next_id(), name.to_string(), definition.to_string());
+        state.schemes.push((playgroup_id, scheme.clone()));
+        Ok(scheme)
+    }
+
+    async fn update_scheme(&self, playgroup_id: i32, id: i32, name: &str, definition: &str) -> Result<Option<SchemeRow>, StoreError> {
+        let mut state = self.state();
+        if state.schemes.iter().any(|(group_id, scheme)| *group_id == playgroup_id && scheme.1 == name && scheme.0 != id) {
+            return Err(StoreError::Duplicate);
+        }
+
+        Ok(state.schemes
+            .iter_mut()
+            .find(|(group_id, scheme)| *group_id == playgroup_id && scheme.0 == id)
+            .map(|(_, scheme)| {
+                scheme.1 = name.to_string();
+                scheme.2 = definition.to_string();
+                scheme.clone()
+            }))
+    }
+
+    async fn delete_scheme(&self, playgroup_id: i32, id: i32) -> Result<bool, StoreError> {
+        let mut state = self.state();
+        let before = state.schemes.len();
+        state.schemes.retain(|(group_id, scheme)| !(*group_id == playgroup_id && scheme.0 == id));
+        Ok(state.schemes.len() < before)
+    }
+}
@@ -0,0 +1,197 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use std::{env, fmt, sync::Arc};
+use ormos::{messages::*, validation::{self, GameError}};
+use crate::{accounts::CurrentUser, playgroups::CurrentPlaygroup, ratings::RatingRow, scoring::SchemeRow};
+
+mod memory;
+mod postgres;
+mod sqlite;
+mod sql;
+
+pub use self::memory::MemoryStore;
+pub use self::postgres::PgStore;
+pub use self::sqlite::SqliteStore;
+
+// What handlers get out of the request extensions, see main
+pub type SharedStore = Arc<dyn Store>;
+
+#[derive(Debug)]
+pub enum StoreError {
+    // Something with the same name already exists
+    Duplicate,
+    // A game named a player who isn't in the playgroup
+    UnknownPlayer(String),
+    // An update left a game that wouldn't pass as a new one
+    Invalid(GameError),
+    // The database holds something we never write, e.g. an unknown audit action
+    Inconsistent(String),
+    Database(sqlx::Error),
+    Migrate(sqlx::migrate::MigrateError),
+    // DATABASE_BACKEND names something connect doesn't know
+    UnknownBackend(String),
+}
+
+impl From<sqlx::Error> for StoreError {
+    fn from(error: sqlx::Error) -> Self {
+        match error.as_database_error() {
+            Some(database_error) if database_error.is_unique_violation() => StoreError::Duplicate,
+            _ => StoreError::Database(error)
+        }
+    }
+}
+
+impl From<GameError> for StoreError {
+    fn from(error: GameError) -> Self {
+        StoreError::Invalid(error)
+    }
+}
+
+impl From<sqlx::migrate::MigrateError> for StoreError {
+    fn from(error: sqlx::migrate::MigrateError) -> Self {
+        StoreError::Migrate(error)
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Duplicate => write!(f, "Already exists"),
+            StoreError::UnknownPlayer(name) => write!(f, "Player \"{}\" does not exist", name),
+            StoreError::Invalid(error) => write!(f, "{}", error),
+            StoreError::Inconsistent(message) => write!(f, "{}", message),
+            StoreError::Database(error) => write!(f, "{}", error),
+            StoreError::Migrate(error) => write!(f, "{}", error),
+            StoreError::UnknownBackend(backend) => write!(f, "Unknown DATABASE_BACKEND \"{}\", expected postgres, sqlite or memory", backend),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+// A game that passed validation, ready to be written
+pub struct NewGame {
+    pub start_datetime: DateTime<Utc>,
+    pub end_datetime: DateTime<Utc>,
+    pub players: Vec<Player>,
+}
+
+// What a PUT or PATCH leaves of a game once it's merged with what is
+// stored. Done under the same lock as the write so two edits at once
+// can't drop each other's changes, and checked like a new game would be.
+fn merge_game(existing: &Game, changes: UpdateGamePayload) -> Result<NewGame, GameError> {
+    let merged = CreateGamePayload {
+        start_datetime: changes.start_datetime.unwrap_or(existing.start_datetime),
+        end_datetime: changes.end_datetime.unwrap_or(existing.end_datetime),
+        players: changes.players.unwrap_or_else(|| existing.players.clone())
+    };
+
+    validation::validate_game(&merged)?;
+
+    Ok(NewGame {
+        start_datetime: merged.start_datetime,
+        end_datetime: merged.end_datetime,
+        players: merged.players
+    })
+}
+
+// id, username, password_hash, admin
+pub type LoginRow = (i32, String, String, bool);
+
+// Everything the handlers read and write, players, games and their
+// commanders included, so the handlers never touch a database directly.
+// Each write that shows up in the audit log records its entry in the same
+// transaction as the write, so there's never a change without an entry or
+// the other way around. That's why those take the ID of the user making it.
+#[async_trait]
+pub trait Store: Send + Sync {
+    async fn playgroup(&self, slug: &str) -> Result<Option<CurrentPlaygroup>, StoreError>;
+    async fn playgroups(&self) -> Result<Vec<Playgroup>, StoreError>;
+    async fn playgroup_ids(&self) -> Result<Vec<i32>, StoreError>;
+    async fn create_playgroup(&self, slug: &str, name: &str) -> Result<(), StoreError>;
+
+    async fn user_count(&self) -> Result<i64, StoreError>;
+    async fn user_id(&self, username: &str) -> Result<Option<i32>, StoreError>;
+    // Only users that haven't been revoked can log in
+    async fn login_user(&self, username: &str) -> Result<Option<LoginRow>, StoreError>;
+    async fn users(&self) -> Result<Vec<User>, StoreError>;
+    async fn create_user(&self, username: &str, password_hash: &str, admin: bool) -> Result<(), StoreError>;
+    // Also throws away the user's sessions
+    async fn revoke_user(&self, user_id: i32) -> Result<(), StoreError>;
+
+    async fn create_session(&self, user_id: i32, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), StoreError>;
+    async fn session_user(&self, token_hash: &str) -> Result<Option<CurrentUser>, StoreError>;
+    async fn delete_session(&self, token_hash: &str) -> Result<(), StoreError>;
+
+    async fn is_member(&self, playgroup_id: i32, user_id: i32) -> Result<bool, StoreError>;
+    async fn members(&self, playgroup_id: i32) -> Result<Vec<String>, StoreError>;
+    async fn add_member(&self, playgroup_id: i32, user_id: i32) -> Result<(), StoreError>;
+    async fn remove_member(&self, playgroup_id: i32, user_id: i32) -> Result<bool, StoreError>;
+
+    async fn api_token_user(&self, token_hash: &str) -> Result<Option<CurrentUser>, StoreError>;
+    async fn api_tokens(&self, user_id: i32) -> Result<Vec<ApiToken>, StoreError>;
+    async fn create_api_token(&self, user_id: i32, name: &str, token_hash: &str, scopes: &[Scope], expires_at: Option<DateTime<Utc>>) -> Result<ApiToken, StoreError>;
+    // Anyone's token when any_user is set, otherwise only the user's own
+    async fn revoke_api_token(&self, id: i32, user_id: i32, any_user: bool) -> Result<bool, StoreError>;
+
+    async fn audit_entries(&self, playgroup_id: i32, query: &AuditQuery, limit: usize) -> Result<Vec<AuditEntry>, StoreError>;
+
+    async fn players(&self, playgroup_id: i32) -> Result<Vec<String>, StoreError>;
+    async fn player_exists(&self, playgroup_id: i32, name: &str) -> Result<bool, StoreError>;
+    async fn create_player(&self, playgroup_id: i32, actor_id: i32, name: &str) -> Result<(), StoreError>;
+
+    // Newest first, deleted games are left out
+    async fn games(&self, playgroup_id: i32, query: &GamesQuery) -> Result<Vec<Game>, StoreError>;
+    async fn game(&self, playgroup_id: i32, id: i32) -> Result<Option<Game>, StoreError>;
+    async fn create_game(&self, playgroup_id: i32, actor_id: i32, game: NewGame) -> Result<Game, StoreError>;
+    // Fields left out of changes keep their stored value, players and
+    // commanders are replaced wholesale. None if there's no such game.
+    async fn update_game(&self, playgroup_id: i32, actor_id: i32, id: i32, changes: UpdateGamePayload) -> Result<Option<Game>, StoreError>;
+    // Games are only marked as deleted so a mistaken delete can be undone.
+    // The game that was deleted or restored, None if there was nothing to do
+    async fn delete_game(&self, playgroup_id: i32, actor_id: i32, id: i32) -> Result<Option<Game>, StoreError>;
+    async fn restore_game(&self, playgroup_id: i32, actor_id: i32, id: i32) -> Result<Option<Game>, StoreError>;
+
+    // Throws away the stored ratings for games starting at or after from and
+    // replays those games with ratings::continue_ratings, picking up from
+    // everyone's last stored rating before then. The game writes above do the
+    // same from the game they change before they commit, since a change to an
+    // old game changes every rating after it but none before, and a game is
+    // never stored without its ratings. Deleted games are included so their
+    // old ratings go too. Without from every game in the playgroup is replayed.
+    async fn rebuild_ratings(&self, playgroup_id: i32, from: Option<DateTime<Utc>>) -> Result<(), StoreError>;
+    // When the oldest game that should have ratings but doesn't started,
+    // e.g. games entered before there were ratings
+    async fn unrated_since(&self, playgroup_id: i32) -> Result<Option<DateTime<Utc>>, StoreError>;
+    // Oldest first, optionally for just one player
+    async fn rating_rows(&self, playgroup_id: i32, name: Option<&str>) -> Result<Vec<RatingRow>, StoreError>;
+
+    async fn seasons(&self, playgroup_id: i32) -> Result<Vec<Season>, StoreError>;
+    async fn season(&self, playgroup_id: i32, id: i32) -> Result<Option<Season>, StoreError>;
+    async fn create_season(&self, playgroup_id: i32, season: &SeasonPayload) -> Result<Season, StoreError>;
+    async fn update_season(&self, playgroup_id: i32, id: i32, season: &SeasonPayload) -> Result<Option<Season>, StoreError>;
+    async fn delete_season(&self, playgroup_id: i32, id: i32) -> Result<bool, StoreError>;
+
+    async fn schemes(&self, playgroup_id: i32) -> Result<Vec<SchemeRow>, StoreError>;
+    async fn scheme(&self, playgroup_id: i32, id: i32) -> Result<Option<SchemeRow>, StoreError>;
+    async fn create_scheme(&self, playgroup_id: i32, name: &str, definition: &str) -> Result<SchemeRow, StoreError>;
+    async fn update_scheme(&self, playgroup_id: i32, id: i32, name: &str, definition: &str) -> Result<Option<SchemeRow>, StoreError>;
+    async fn delete_scheme(&self, playgroup_id: i32, id: i32) -> Result<bool, StoreError>;
+}
+
+// DATABASE_BACKEND picks where everything is kept. Postgres is the default,
+// sqlite keeps everything in the single file at SQLITE_PATH instead and
+// memory keeps nothing past a restart.
+pub async fn connect() -> Result<SharedStore, StoreError> {
+    let backend = env::var("DATABASE_BACKEND").unwrap_or(String::from("postgres"));
+
+    match backend.as_str() {
+        "postgres" => Ok(Arc::new(PgStore::connect().await?)),
+        "sqlite" => {
+            let path = env::var("SQLITE_PATH").unwrap_or(String::from("ormos.db"));
+            Ok(Arc::new(SqliteStore::connect(&path).await?))
+        },
+        "memory" => Ok(Arc::new(MemoryStore::new())),
+        _ => Err(StoreError::UnknownBackend(backend))
+    }
+}
@@ -0,0 +1,93 @@
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::env;
+use super::{StoreError, sql::sql_store};
+
+// The default, see connect
+pub struct PgStore {
+    pool: PgPool,
+}
+
+impl PgStore {
+    pub async fn connect() -> Result<Self, StoreError> {
+        // Defaults values correspond to development postgres, not production
+        let pg_user = env::var("POSTGRES_USER").unwrap_or(String::from("postgres"));
+        let pg_password = env::var("POSTGRES_PASSWORD").unwrap_or(String::from("password"));
+        let pg_host = env::var("POSTGRES_HOST").unwrap_or(String::from("localhost"));
+        let pg_port = env::var("POSTGRES_PORT").unwrap_or(String::from("55432"));
+        let pg_database = env::var("POSTGRES_DB").unwrap_or(String::from("ormos"));
+
+        let connection_string = format!("postgres://{}:{}@{}:{}/{}", pg_user, pg_password, pg_host, pg_port, pg_database);
+
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect(connection_string.as_str()).await?;
+
+        // Migrations live in migrations/postgres and are compiled into the binary.
+        // Anything not yet applied to the database runs here, in order.
+        sqlx::migrate!("./migrations/postgres").run(&pool).await?;
+
+        Ok(PgStore { pool })
+    }
+}
+
+// Builds every game in one query. Players come out ordered by rank then
+// name and commanders in the order they were submitted, so a game always
+// serializes the same way. Players without commanders and games without
+// players get empty lists rather than disappearing.
+const GAME_SELECT: &str = "SELECT games.id, games.start_datetime, games.end_datetime,
+    COALESCE((
+        SELECT json_agg(json_build_object(
+            'id', players.id,
+            'name', players.name,
+            'rank', games_players.rank,
+            'commanders', COALESCE((
+                SELECT json_agg(commander ORDER BY commanders.id)
+                FROM commanders WHERE games_players_id = games_players.id
+            ), '[]'::json)
+        ) ORDER BY games_players.rank, players.name)
+        FROM games_players INNER JOIN players ON player_id = players.id
+        WHERE game_id = games.id
+    ), '[]'::json) AS players,
+    (SELECT username FROM users WHERE users.id = games.submitted_by) AS submitted_by
+    FROM games
+    WHERE games.deleted_at IS NULL";
+
+// Every filter is optional, a NULL parameter turns its condition off.
+// Games are ordered newest first with the ID as a tie breaker so
+// the order is stable and can be paged through with the before cursor.
+const GAME_FILTER: &str = "
+    AND ($1::timestamptz IS NULL OR games.start_datetime >= $1)
+    AND ($2::timestamptz IS NULL OR games.start_datetime < $2)
+    AND ($3::text IS NULL OR EXISTS (
+        SELECT 1 FROM games_players INNER JOIN players ON player_id = players.id
+        WHERE game_id = games.id AND players.name = $3))
+    AND ($4::text IS NULL OR EXISTS (
+        SELECT 1 FROM games_players INNER JOIN commanders ON games_players_id = games_players.id
+        WHERE game_id = games.id AND commander = $4))
+    AND ($5::bigint IS NULL OR (SELECT COUNT(*) FROM games_players WHERE game_id = games.id) = $5)
+    AND ($6::integer IS NULL OR (games.start_datetime, games.id) < (SELECT start_datetime, id FROM games WHERE id = $6 AND playgroup_id = $8))
+    AND games.playgroup_id = $8
+    ORDER BY games.start_datetime DESC, games.id DESC
+    LIMIT $7";
+
+// Same approach as GAME_FILTER, a NULL parameter turns its condition off
+const AUDIT_SELECT: &str = "SELECT audit_log.id, users.username, entity, entity_id, action, created_at, before, after
+    FROM audit_log LEFT JOIN users ON actor_id = users.id
+    WHERE playgroup_id = $1
+    AND ($2::text IS NULL OR entity = $2)
+    AND ($3::integer IS NULL OR entity_id = $3)
+    AND ($4::text IS NULL OR action = $4)
+    AND ($5::text IS NULL OR users.username = $5)
+    AND ($6::timestamptz IS NULL OR created_at >= $6)
+    AND ($7::timestamptz IS NULL OR created_at < $7)
+    AND ($8::integer IS NULL OR audit_log.id < $8)
+    ORDER BY audit_log.id DESC
+    LIMIT $9";
+
+// Keeps a game from changing between reading it and writing it back
+const LOCK_GAME: &str = " FOR UPDATE OF games";
+
+// Without the lock two rebuilds could interleave and leave both sets of rows behind
+const LOCK_RATINGS: Option<&str> = Some("LOCK TABLE ratings IN EXCLUSIVE MODE");
+
+sql_store!(PgStore, sqlx::Postgres, Vec<String>);
@@ -0,0 +1,629 @@
+use chrono::{DateTime, Utc};
+use sqlx::types::Json as SqlJson;
+use ormos::messages::*;
+use crate::{api_tokens, audit};
+use super::StoreError;
+
+// What postgres and sqlite have in common, which is nearly everything.
+// The queries that need dialect specific SQL (JSON aggregation, typed NULL
+// parameters and locking) are defined by each backend, see sql_store.
+
+#[derive(sqlx::FromRow)]
+pub struct GameRow {
+    id: i32,
+    start_datetime: DateTime<Utc>,
+    end_datetime: DateTime<Utc>,
+    players: SqlJson<Vec<Player>>,
+    submitted_by: Option<String>
+}
+
+impl From<GameRow> for Game {
+    fn from(row: GameRow) -> Self {
+        Game {
+            id: row.id,
+            start_datetime: row.start_datetime,
+            end_datetime: row.end_datetime,
+            players: row.players.0,
+            submitted_by: row.submitted_by
+        }
+    }
+}
+
+// id, actor, entity, entity_id, action, created_at, before, after
+pub type AuditRow = (i32, Option<String>, String, i32, String, DateTime<Utc>, Option<SqlJson<serde_json::Value>>, Option<SqlJson<serde_json::Value>>);
+
+pub fn audit_entry_from_row((id, actor, entity, entity_id, action, timestamp, before, after): AuditRow) -> Result<AuditEntry, StoreError> {
+    Ok(AuditEntry {
+        id,
+        actor,
+        entity: audit::parse_entity(&entity)?,
+        entity_id,
+        action: audit::parse_action(&action)?,
+        timestamp,
+        before: before.map(|before| before.0),
+        after: after.map(|after| after.0),
+    })
+}
+
+// How a backend keeps an API token's scopes, postgres has arrays and sqlite has JSON
+pub trait ScopeColumn {
+    fn from_scopes(scopes: &[Scope]) -> Self;
+    fn names(self) -> Vec<String>;
+}
+
+impl ScopeColumn for Vec<String> {
+    fn from_scopes(scopes: &[Scope]) -> Self {
+        scopes.iter().map(|scope| scope.as_str().to_string()).collect()
+    }
+
+    fn names(self) -> Vec<String> {
+        self
+    }
+}
+
+impl ScopeColumn for SqlJson<Vec<String>> {
+    fn from_scopes(scopes: &[Scope]) -> Self {
+        SqlJson(Vec::from_scopes(scopes))
+    }
+
+    fn names(self) -> Vec<String> {
+        self.0
+    }
+}
+
+// id, name, scopes, created_at, expires_at, revoked
+pub type ApiTokenRow<S> = (i32, String, S, DateTime<Utc>, Option<DateTime<Utc>>, bool);
+
+pub const API_TOKEN_SELECT: &str = "SELECT id, name, scopes, created_at, expires_at, revoked_at IS NOT NULL FROM api_tokens";
+
+pub fn api_token_from_row<S: ScopeColumn>((id, name, scopes, created_at, expires_at, revoked): ApiTokenRow<S>) -> ApiToken {
+    ApiToken {
+        id,
+        name,
+        scopes: api_tokens::parse_scopes(scopes.names()),
+        created_at,
+        expires_at,
+        revoked,
+    }
+}
+
+pub const RATING_SELECT: &str = "SELECT players.name, games.id, games.start_datetime, rating_before, rating_after
+    FROM ratings
+    INNER JOIN players ON player_id = players.id
+    INNER JOIN games ON game_id = games.id
+    WHERE games.playgroup_id = $1";
+
+pub const RATING_ORDER: &str = "ORDER BY games.start_datetime, games.id";
+
+// Each player's rating after their last game that started before $2
+pub const LATEST_RATINGS: &str = "SELECT player_id, rating_after FROM (
+        SELECT player_id, rating_after, ROW_NUMBER() OVER (
+            PARTITION BY player_id ORDER BY games.start_datetime DESC, games.id DESC
+        ) AS position
+        FROM ratings INNER JOIN games ON game_id = games.id
+        WHERE games.playgroup_id = $1 AND games.start_datetime < $2
+    ) AS latest
+    WHERE position = 1";
+
+// Games with at least two players get ratings, see ratings::compute_ratings
+pub const UNRATED_SINCE: &str = "SELECT MIN(start_datetime) FROM games
+    WHERE playgroup_id = $1 AND deleted_at IS NULL
+    AND NOT EXISTS (SELECT 1 FROM ratings WHERE game_id = games.id)
+    AND (SELECT COUNT(*) FROM games_players WHERE game_id = games.id) > 1";
+
+// id, name, start_datetime, end_datetime
+pub type SeasonRow = (i32, String, DateTime<Utc>, DateTime<Utc>);
+
+pub const SEASON_SELECT: &str = "SELECT id, name, start_datetime, end_datetime FROM seasons";
+
+pub fn season_from_row((id, name, start_datetime, end_datetime): SeasonRow) -> Season {
+    Season {
+        id,
+        name,
+        start_datetime,
+        end_datetime,
+    }
+}
+
+pub const SCHEME_SELECT: &str = "SELECT id, name, definition FROM scoring_schemes";
+
+// Implements Store for a backend's pool. The SQL is written once with $1
+// style parameters, which sqlite understands as well. The module it's used in
+// provides the dialect specific parts:
+//
+// GAME_SELECT: every game with its players as a JSON array, see GameRow
+// GAME_FILTER: the GamesQuery conditions, ordering and limit
+// AUDIT_SELECT: the AuditQuery conditions, ordering and limit
+// LOCK_GAME: appended to a game's SELECT to lock it for an update
+// LOCK_RATINGS: run before the ratings are rebuilt, if anything
+//
+// $scopes is how the api_tokens.scopes column is read and written.
+macro_rules! sql_store {
+    ($store:ident, $db:ty, $scopes:ty) => {
+        mod shared {
+            use axum::async_trait;
+            use chrono::{DateTime, Utc};
+            use std::collections::HashMap;
+            use sqlx::{Transaction, types::Json as SqlJson};
+            use ormos::messages::*;
+            use crate::{accounts::{self, CurrentUser}, api_tokens, audit, playgroups::CurrentPlaygroup, ratings::{self, RatingRow}, scoring::SchemeRow};
+            use crate::store::{LoginRow, NewGame, Store, StoreError, merge_game, sql::*};
+            use super::{$store, AUDIT_SELECT, GAME_FILTER, GAME_SELECT, LOCK_GAME, LOCK_RATINGS};
+
+            async fn fetch_games<'e, E: sqlx::Executor<'e, Database = $db>>(executor: E, playgroup_id: i32, query: &GamesQuery) -> Result<Vec<Game>, sqlx::Error> {
+                let rows: Vec<GameRow> = sqlx::query_as(&format!("{}{}", GAME_SELECT, GAME_FILTER))
+                    .bind(query.from)
+                    .bind(query.to)
+                    .bind(&query.player)
+                    .bind(&query.commander)
+                    .bind(query.players.map(|players| players as i64))
+                    .bind(query.before)
+                    .bind(query.limit.map(|limit| limit as i64))
+                    .bind(playgroup_id)
+                    .fetch_all(executor).await?;
+
+                Ok(rows.into_iter().map(Game::from).collect())
+            }
+
+            async fn fetch_game<'e, E: sqlx::Executor<'e, Database = $db>>(executor: E, playgroup_id: i32, id: i32) -> Result<Option<Game>, sqlx::Error> {
+                let row: Option<GameRow> = sqlx::query_as(&format!("{} AND games.id = $1 AND games.playgroup_id = $2", GAME_SELECT)).bind(id).bind(playgroup_id).fetch_optional(executor).await?;
+                Ok(row.map(Game::from))
+            }
+
+            // Inserts the games_players and commanders rows for every player in a game
+            async fn insert_game_players(tx: &mut Transaction<'_, $db>, playgroup_id: i32, game_id: i32, players: Vec<Player>) -> Result<(), StoreError> {
+                for player in players {
+                    let player_row: Option<(i32,)> = sqlx::query_as("SELECT id FROM players WHERE name = $1 AND playgroup_id = $2").bind(&player.name).bind(playgroup_id).fetch_optional(&mut **tx).await?;
+
+                    let Some((player_id,)) = player_row else {
+                        return Err(StoreError::UnknownPlayer(player.name));
+                    };
+
+                    let (games_players_id,): (i32,) = sqlx::query_as("INSERT INTO games_players (game_id, player_id, rank) VALUES($1, $2, $3) RETURNING id").bind(game_id).bind(player_id).bind(player.rank as i32).fetch_one(&mut **tx).await?;
+                    for commander in player.commanders {
+                        sqlx::query("INSERT INTO commanders (games_players_id, commander) VALUES($1, $2)").bind(games_players_id).bind(commander).execute(&mut **tx).await?;
+                    }
+                }
+
+                Ok(())
+            }
+
+            async fn record(tx: &mut Transaction<'_, $db>, playgroup_id: i32, actor_id: i32, entity: AuditEntity, entity_id: i32, action: AuditAction, change: audit::Change) -> Result<(), sqlx::Error> {
+                sqlx::query("INSERT INTO audit_log (playgroup_id, actor_id, entity, entity_id, action, created_at, before, after) VALUES($1, $2, $3, $4, $5, $6, $7, $8)")
+                    .bind(playgroup_id)
+                    .bind(actor_id)
+                    .bind(audit::entity_str(entity))
+                    .bind(entity_id)
+                    .bind(audit::action_str(action))
+                    .bind(Utc::now())
+                    .bind(change.before.map(SqlJson))
+                    .bind(change.after.map(SqlJson))
+                    .execute(&mut **tx).await?;
+
+                Ok(())
+            }
+
+            // See Store::rebuild_ratings, the game writes call this before they commit
+            async fn replay_ratings(tx: &mut Transaction<'_, $db>, playgroup_id: i32, from: Option<DateTime<Utc>>) -> Result<(), StoreError> {
+                if let Some(lock) = LOCK_RATINGS {
+                    sqlx::query(lock).execute(&mut **tx).await?;
+                }
+
+                let games = fetch_games(&mut **tx, playgroup_id, &GamesQuery { from, ..GamesQuery::default() }).await?;
+
+                let starting = match from {
+                    Some(from) => {
+                        let rows: Vec<(i32, f64)> = sqlx::query_as(LATEST_RATINGS).bind(playgroup_id).bind(from).fetch_all(&mut **tx).await?;
+                        sqlx::query("DELETE FROM ratings WHERE game_id IN (SELECT id FROM games WHERE playgroup_id = $1 AND start_datetime >= $2)").bind(playgroup_id).bind(from).execute(&mut **tx).await?;
+                        rows.into_iter().collect()
+                    },
+                    None => {
+                        sqlx::query("DELETE FROM ratings WHERE game_id IN (SELECT id FROM games WHERE playgroup_id = $1)").bind(playgroup_id).execute(&mut **tx).await?;
+                        HashMap::new()
+                    }
+                };
+
+                for change in ratings::continue_ratings(&games, starting) {
+                    sqlx::query("INSERT INTO ratings (game_id, player_id, rating_before, rating_after) VALUES($1, $2, $3, $4)")
+                        .bind(change.game_id)
+                        .bind(change.player_id)
+                        .bind(change.rating_before)
+                        .bind(change.rating_after)
+                        .execute(&mut **tx).await?;
+                }
+
+                Ok(())
+            }
+
+            #[async_trait]
+            impl Store for $store {
+                async fn playgroup(&self, slug: &str) -> Result<Option<CurrentPlaygroup>, StoreError> {
+                    let row: Option<(i32, String)> = sqlx::query_as("SELECT id, slug FROM playgroups WHERE slug = $1").bind(slug).fetch_optional(&self.pool).await?;
+                    Ok(row.map(|(id, slug)| CurrentPlaygroup { id, slug }))
+                }
+
+                async fn playgroups(&self) -> Result<Vec<Playgroup>, StoreError> {
+                    let rows: Vec<(String, String)> = sqlx::query_as("SELECT slug, name FROM playgroups ORDER BY slug").fetch_all(&self.pool).await?;
+                    Ok(rows.into_iter().map(|(slug, name)| Playgroup { slug, name }).collect())
+                }
+
+                async fn playgroup_ids(&self) -> Result<Vec<i32>, StoreError> {
+                    let rows: Vec<(i32,)> = sqlx::query_as("SELECT id FROM playgroups ORDER BY id").fetch_all(&self.pool).await?;
+                    Ok(rows.into_iter().map(|row| row.0).collect())
+                }
+
+                async fn create_playgroup(&self, slug: &str, name: &str) -> Result<(), StoreError> {
+                    sqlx::query("INSERT INTO playgroups (slug, name) VALUES($1, $2)").bind(slug).bind(name).execute(&self.pool).await?;
+                    Ok(())
+                }
+
+                async fn user_count(&self) -> Result<i64, StoreError> {
+                    let row: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users").fetch_one(&self.pool).await?;
+                    Ok(row.0)
+                }
+
+                async fn user_id(&self, username: &str) -> Result<Option<i32>, StoreError> {
+                    let row: Option<(i32,)> = sqlx::query_as("SELECT id FROM users WHERE username = $1").bind(username).fetch_optional(&self.pool).await?;
+                    Ok(row.map(|row| row.0))
+                }
+
+                async fn login_user(&self, username: &str) -> Result<Option<LoginRow>, StoreError> {
+                    let row: Option<LoginRow> = sqlx::query_as("SELECT id, username, password_hash, admin FROM users WHERE username = $1 AND revoked_at IS NULL").bind(username).fetch_optional(&self.pool).await?;
+                    Ok(row)
+                }
+
+                async fn users(&self) -> Result<Vec<User>, StoreError> {
+                    let rows: Vec<(String, bool, bool)> = sqlx::query_as("SELECT username, admin, revoked_at IS NOT NULL FROM users ORDER BY username").fetch_all(&self.pool).await?;
+                    Ok(rows.into_iter().map(|(username, admin, revoked)| User { username, admin, revoked }).collect())
+                }
+
+                async fn create_user(&self, username: &str, password_hash: &str, admin: bool) -> Result<(), StoreError> {
+                    sqlx::query("INSERT INTO users (username, password_hash, admin) VALUES($1, $2, $3)").bind(username).bind(password_hash).bind(admin).execute(&self.pool).await?;
+                    Ok(())
+                }
+
+                async fn revoke_user(&self, user_id: i32) -> Result<(), StoreError> {
+                    let mut tx = self.pool.begin().await?;
+                    sqlx::query("UPDATE users SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL").bind(Utc::now()).bind(user_id).execute(&mut *tx).await?;
+                    sqlx::query("DELETE FROM sessions WHERE user_id = $1").bind(user_id).execute(&mut *tx).await?;
+                    tx.commit().await?;
+                    Ok(())
+                }
+
+                async fn create_session(&self, user_id: i32, token_hash: &str, expires_at: DateTime<Utc>) -> Result<(), StoreError> {
+                    sqlx::query("INSERT INTO sessions (user_id, token_hash, created_at, expires_at) VALUES($1, $2, $3, $4)")
+                        .bind(user_id)
+                        .bind(token_hash)
+                        .bind(Utc::now())
+                        .bind(expires_at)
+                        .execute(&self.pool).await?;
+                    Ok(())
+                }
+
+                async fn session_user(&self, token_hash: &str) -> Result<Option<CurrentUser>, StoreError> {
+                    let row: Option<(i32, String, bool)> = sqlx::query_as("SELECT users.id, users.username, users.admin
+                            FROM sessions INNER JOIN users ON user_id = users.id
+                            WHERE token_hash = $1 AND expires_at > $2 AND users.revoked_at IS NULL")
+                        .bind(token_hash)
+                        .bind(Utc::now())
+                        .fetch_optional(&self.pool).await?;
+
+                    Ok(row.map(|(id, username, admin)| CurrentUser { id, username, admin, scopes: accounts::allowed_scopes(admin), api_token: false }))
+                }
+
+                async fn delete_session(&self, token_hash: &str) -> Result<(), StoreError> {
+                    sqlx::query("DELETE FROM sessions WHERE token_hash = $1").bind(token_hash).execute(&self.pool).await?;
+                    Ok(())
+                }
+
+                async fn is_member(&self, playgroup_id: i32, user_id: i32) -> Result<bool, StoreError> {
+                    let row: Option<(i32,)> = sqlx::query_as("SELECT user_id FROM playgroup_members WHERE playgroup_id = $1 AND user_id = $2").bind(playgroup_id).bind(user_id).fetch_optional(&self.pool).await?;
+                    Ok(row.is_some())
+                }
+
+                async fn members(&self, playgroup_id: i32) -> Result<Vec<String>, StoreError> {
+                    let rows: Vec<(String,)> = sqlx::query_as("SELECT username FROM playgroup_members INNER JOIN users ON user_id = users.id
+                            WHERE playgroup_id = $1 ORDER BY username")
+                        .bind(playgroup_id)
+                        .fetch_all(&self.pool).await?;
+                    Ok(rows.into_iter().map(|row| row.0).collect())
+                }
+
+                async fn add_member(&self, playgroup_id: i32, user_id: i32) -> Result<(), StoreError> {
+                    sqlx::query("INSERT INTO playgroup_members (playgroup_id, user_id) VALUES($1, $2) ON CONFLICT DO NOTHING").bind(playgroup_id).bind(user_id).execute(&self.pool).await?;
+                    Ok(())
+                }
+
+                async fn remove_member(&self, playgroup_id: i32, user_id: i32) -> Result<bool, StoreError> {
+                    let result = sqlx::query("DELETE FROM playgroup_members WHERE playgroup_id = $1 AND user_id = $2").bind(playgroup_id).bind(user_id).execute(&self.pool).await?;
+                    Ok(result.rows_affected() > 0)
+                }
+
+                async fn api_token_user(&self, token_hash: &str) -> Result<Option<CurrentUser>, StoreError> {
+                    let row: Option<(i32, String, bool, $scopes)> = sqlx::query_as("SELECT users.id, users.username, users.admin, api_tokens.scopes
+                            FROM api_tokens INNER JOIN users ON user_id = users.id
+                            WHERE token_hash = $1
+                            AND api_tokens.revoked_at IS NULL
+                            AND (expires_at IS NULL OR expires_at > $2)
+                            AND users.revoked_at IS NULL")
+                        .bind(token_hash)
+                        .bind(Utc::now())
+                        .fetch_optional(&self.pool).await?;
+
+                    Ok(row.map(|(id, username, admin, scopes)| CurrentUser {
+                        id,
+                        username,
+                        admin,
+                        scopes: api_tokens::parse_scopes(scopes.names()),
+                        api_token: true
+                    }))
+                }
+
+                async fn api_tokens(&self, user_id: i32) -> Result<Vec<ApiToken>, StoreError> {
+                    let rows: Vec<ApiTokenRow<$scopes>> = sqlx::query_as(&format!("{} WHERE user_id = $1 ORDER BY created_at DESC, id DESC", API_TOKEN_SELECT)).bind(user_id).fetch_all(&self.pool).await?;
+                    Ok(rows.into_iter().map(api_token_from_row).collect())
+                }
+
+                async fn create_api_token(&self, user_id: i32, name: &str, token_hash: &str, scopes: &[Scope], expires_at: Option<DateTime<Utc>>) -> Result<ApiToken, StoreError> {
+                    let scopes = <$scopes>::from_scopes(scopes);
+
+                    let row: ApiTokenRow<$scopes> = sqlx::query_as("INSERT INTO api_tokens (user_id, name, token_hash, scopes, created_at, expires_at) VALUES($1, $2, $3, $4, $5, $6)
+                            RETURNING id, name, scopes, created_at, expires_at, revoked_at IS NOT NULL")
+                        .bind(user_id)
+                        .bind(name)
+                        .bind(token_hash)
+                        .bind(scopes)
+                        .bind(Utc::now())
+                        .bind(expires_at)
+                        .fetch_one(&self.pool).await?;
+
+                    Ok(api_token_from_row(row))
+                }
+
+                async fn revoke_api_token(&self, id: i32, user_id: i32, any_user: bool) -> Result<bool, StoreError> {
+                    let result = sqlx::query("UPDATE api_tokens SET revoked_at = $1 WHERE id = $2 AND (user_id = $3 OR $4) AND revoked_at IS NULL")
+                        .bind(Utc::now())
+                        .bind(id)
+                        .bind(user_id)
+                        .bind(any_user)
+                        .execute(&self.pool).await?;
+
+                    Ok(result.rows_affected() > 0)
+                }
+
+                async fn audit_entries(&self, playgroup_id: i32, query: &AuditQuery, limit: usize) -> Result<Vec<AuditEntry>, StoreError> {
+                    let rows: Vec<AuditRow> = sqlx::query_as(AUDIT_SELECT)
+                        .bind(playgroup_id)
+                        .bind(query.entity.map(audit::entity_str))
+                        .bind(query.entity_id)
+                        .bind(query.action.map(audit::action_str))
+                        .bind(&query.actor)
+                        .bind(query.from)
+                        .bind(query.to)
+                        .bind(query.before)
+                        .bind(limit as i64)
+                        .fetch_all(&self.pool).await?;
+
+                    rows.into_iter().map(audit_entry_from_row).collect()
+                }
+
+                async fn players(&self, playgroup_id: i32) -> Result<Vec<String>, StoreError> {
+                    let rows: Vec<(String,)> = sqlx::query_as("SELECT name FROM players WHERE playgroup_id = $1").bind(playgroup_id).fetch_all(&self.pool).await?;
+                    Ok(rows.into_iter().map(|row| row.0).collect())
+                }
+
+                async fn player_exists(&self, playgroup_id: i32, name: &str) -> Result<bool, StoreError> {
+                    let row: Option<(i32,)> = sqlx::query_as("SELECT id FROM players WHERE name = $1 AND playgroup_id = $2").bind(name).bind(playgroup_id).fetch_optional(&self.pool).await?;
+                    Ok(row.is_some())
+                }
+
+                async fn create_player(&self, playgroup_id: i32, actor_id: i32, name: &str) -> Result<(), StoreError> {
+                    let mut tx = self.pool.begin().await?;
+
+                    let (player_id,): (i32,) = sqlx::query_as("INSERT INTO players (playgroup_id, name, created_by) VALUES($1, $2, $3) RETURNING id").bind(playgroup_id).bind(name).bind(actor_id).fetch_one(&mut *tx).await?;
+
+                    record(&mut tx, playgroup_id, actor_id, AuditEntity::Player, player_id, AuditAction::Create, audit::Change {
+                        before: None,
+                        after: Some(serde_json::json!({ "id": player_id, "name": name }))
+                    }).await?;
+
+                    tx.commit().await?;
+                    Ok(())
+                }
+
+                async fn games(&self, playgroup_id: i32, query: &GamesQuery) -> Result<Vec<Game>, StoreError> {
+                    Ok(fetch_games(&self.pool, playgroup_id, query).await?)
+                }
+
+                async fn game(&self, playgroup_id: i32, id: i32) -> Result<Option<Game>, StoreError> {
+                    Ok(fetch_game(&self.pool, playgroup_id, id).await?)
+                }
+
+                async fn create_game(&self, playgroup_id: i32, actor_id: i32, game: NewGame) -> Result<Game, StoreError> {
+                    let mut tx = self.pool.begin().await?;
+
+                    let (game_id,): (i32,) = sqlx::query_as("INSERT INTO games (playgroup_id, start_datetime, end_datetime, submitted_by) VALUES($1, $2, $3, $4) RETURNING id").bind(playgroup_id).bind(game.start_datetime).bind(game.end_datetime).bind(actor_id).fetch_one(&mut *tx).await?;
+
+                    insert_game_players(&mut tx, playgroup_id, game_id, game.players).await?;
+
+                    let created = fetch_game(&mut *tx, playgroup_id, game_id).await?;
+                    record(&mut tx, playgroup_id, actor_id, AuditEntity::Game, game_id, AuditAction::Create, audit::Change {
+                        before: None,
+                        after: audit::snapshot(&created)
+                    }).await?;
+
+                    replay_ratings(&mut tx, playgroup_id, Some(game.start_datetime)).await?;
+
+                    tx.commit().await?;
+
+                    created.ok_or_else(|| StoreError::Inconsistent(format!("Game {} disappeared while it was being created", game_id)))
+                }
+
+                async fn update_game(&self, playgroup_id: i32, actor_id: i32, id: i32, changes: UpdateGamePayload) -> Result<Option<Game>, StoreError> {
+                    let mut tx = self.pool.begin().await?;
+
+                    let row: Option<GameRow> = sqlx::query_as(&format!("{} AND games.id = $1 AND games.playgroup_id = $2{}", GAME_SELECT, LOCK_GAME)).bind(id).bind(playgroup_id).fetch_optional(&mut *tx).await?;
+                    let Some(existing) = row.map(Game::from) else {
+                        return Ok(None);
+                    };
+                    let game = merge_game(&existing, changes)?;
+
+                    sqlx::query("UPDATE games SET start_datetime = $1, end_datetime = $2, updated_by = $3 WHERE id = $4").bind(game.start_datetime).bind(game.end_datetime).bind(actor_id).bind(id).execute(&mut *tx).await?;
+                    // Commanders go along with their games_players rows
+                    sqlx::query("DELETE FROM games_players WHERE game_id = $1").bind(id).execute(&mut *tx).await?;
+
+                    insert_game_players(&mut tx, playgroup_id, id, game.players).await?;
+
+                    // Moving a game changes the ratings from wherever it was or now is, whichever is first
+                    let from = existing.start_datetime.min(game.start_datetime);
+
+                    let updated = fetch_game(&mut *tx, playgroup_id, id).await?;
+                    record(&mut tx, playgroup_id, actor_id, AuditEntity::Game, id, AuditAction::Update, audit::Change {
+                        before: audit::snapshot(&Some(existing)),
+                        after: audit::snapshot(&updated)
+                    }).await?;
+
+                    replay_ratings(&mut tx, playgroup_id, Some(from)).await?;
+
+                    tx.commit().await?;
+
+                    Ok(updated)
+                }
+
+                async fn delete_game(&self, playgroup_id: i32, actor_id: i32, id: i32) -> Result<Option<Game>, StoreError> {
+                    let mut tx = self.pool.begin().await?;
+
+                    let existing = fetch_game(&mut *tx, playgroup_id, id).await?;
+                    let result = sqlx::query("UPDATE games SET deleted_at = $1, updated_by = $2 WHERE id = $3 AND playgroup_id = $4 AND deleted_at IS NULL").bind(Utc::now()).bind(actor_id).bind(id).bind(playgroup_id).execute(&mut *tx).await?;
+
+                    if result.rows_affected() == 0 {
+                        return Ok(None);
+                    }
+
+                    record(&mut tx, playgroup_id, actor_id, AuditEntity::Game, id, AuditAction::Delete, audit::Change {
+                        before: audit::snapshot(&existing),
+                        after: None
+                    }).await?;
+
+                    replay_ratings(&mut tx, playgroup_id, existing.as_ref().map(|game| game.start_datetime)).await?;
+
+                    tx.commit().await?;
+                    Ok(existing)
+                }
+
+                async fn restore_game(&self, playgroup_id: i32, actor_id: i32, id: i32) -> Result<Option<Game>, StoreError> {
+                    let mut tx = self.pool.begin().await?;
+
+                    let result = sqlx::query("UPDATE games SET deleted_at = NULL, updated_by = $1 WHERE id = $2 AND playgroup_id = $3 AND deleted_at IS NOT NULL").bind(actor_id).bind(id).bind(playgroup_id).execute(&mut *tx).await?;
+
+                    if result.rows_affected() == 0 {
+                        return Ok(None);
+                    }
+
+                    let restored = fetch_game(&mut *tx, playgroup_id, id).await?;
+                    record(&mut tx, playgroup_id, actor_id, AuditEntity::Game, id, AuditAction::Restore, audit::Change {
+                        before: None,
+                        after: audit::snapshot(&restored)
+                    }).await?;
+
+                    replay_ratings(&mut tx, playgroup_id, restored.as_ref().map(|game| game.start_datetime)).await?;
+
+                    tx.commit().await?;
+                    Ok(restored)
+                }
+
+                async fn rebuild_ratings(&self, playgroup_id: i32, from: Option<DateTime<Utc>>) -> Result<(), StoreError> {
+                    let mut tx = self.pool.begin().await?;
+                    replay_ratings(&mut tx, playgroup_id, from).await?;
+                    tx.commit().await?;
+                    Ok(())
+                }
+
+                async fn unrated_since(&self, playgroup_id: i32) -> Result<Option<DateTime<Utc>>, StoreError> {
+                    let row: (Option<DateTime<Utc>>,) = sqlx::query_as(UNRATED_SINCE).bind(playgroup_id).fetch_one(&self.pool).await?;
+                    Ok(row.0)
+                }
+
+                async fn rating_rows(&self, playgroup_id: i32, name: Option<&str>) -> Result<Vec<RatingRow>, StoreError> {
+                    let rows = match name {
+                        Some(name) => sqlx::query_as(&format!("{} AND players.name = $2 {}", RATING_SELECT, RATING_ORDER)).bind(playgroup_id).bind(name).fetch_all(&self.pool).await?,
+                        None => sqlx::query_as(&format!("{} {}", RATING_SELECT, RATING_ORDER)).bind(playgroup_id).fetch_all(&self.pool).await?
+                    };
+                    Ok(rows)
+                }
+
+                async fn seasons(&self, playgroup_id: i32) -> Result<Vec<Season>, StoreError> {
+                    let rows: Vec<SeasonRow> = sqlx::query_as(&format!("{} WHERE playgroup_id = $1 ORDER BY start_datetime DESC, id DESC", SEASON_SELECT)).bind(playgroup_id).fetch_all(&self.pool).await?;
+                    Ok(rows.into_iter().map(season_from_row).collect())
+                }
+
+                async fn season(&self, playgroup_id: i32, id: i32) -> Result<Option<Season>, StoreError> {
+                    let row: Option<SeasonRow> = sqlx::query_as(&format!("{} WHERE id = $1 AND playgroup_id = $2", SEASON_SELECT)).bind(id).bind(playgroup_id).fetch_optional(&self.pool).await?;
+                    Ok(row.map(season_from_row))
+                }
+
+                async fn create_season(&self, playgroup_id: i32, season: &SeasonPayload) -> Result<Season, StoreError> {
+                    let row: SeasonRow = sqlx::query_as("INSERT INTO seasons (playgroup_id, name, start_datetime, end_datetime) VALUES($1, $2, $3, $4) RETURNING id, name, start_datetime, end_datetime")
+                        .bind(playgroup_id)
+                        .bind(&season.name)
+                        .bind(season.start_datetime)
+                        .bind(season.end_datetime)
+                        .fetch_one(&self.pool).await?;
+                    Ok(season_from_row(row))
+                }
+
+                async fn update_season(&self, playgroup_id: i32, id: i32, season: &SeasonPayload) -> Result<Option<Season>, StoreError> {
+                    let row: Option<SeasonRow> = sqlx::query_as("UPDATE seasons SET name = $1, start_datetime = $2, end_datetime = $3 WHERE id = $4 AND playgroup_id = $5 RETURNING id, name, start_datetime, end_datetime")
+                        .bind(&season.name)
+                        .bind(season.start_datetime)
+                        .bind(season.end_datetime)
+                        .bind(id)
+                        .bind(playgroup_id)
+                        .fetch_optional(&self.pool).await?;
+                    Ok(row.map(season_from_row))
+                }
+
+                async fn delete_season(&self, playgroup_id: i32, id: i32) -> Result<bool, StoreError> {
+                    let result = sqlx::query("DELETE FROM seasons WHERE id = $1 AND playgroup_id = $2").bind(id).bind(playgroup_id).execute(&self.pool).await?;
+                    Ok(result.rows_affected() > 0)
+                }
+
+                async fn schemes(&self, playgroup_id: i32) -> Result<Vec<SchemeRow>, StoreError> {
+                    let rows: Vec<SchemeRow> = sqlx::query_as(&format!("{} WHERE playgroup_id = $1 ORDER BY name", SCHEME_SELECT)).bind(playgroup_id).fetch_all(&self.pool).await?;
+                    Ok(rows)
+                }
+
+                async fn scheme(&self, playgroup_id: i32, id: i32) -> Result<Option<SchemeRow>, StoreError> {
+                    let row: Option<SchemeRow> = sqlx::query_as(&format!("{} WHERE id = $1 AND playgroup_id = $2", SCHEME_SELECT)).bind(id).bind(playgroup_id).fetch_optional(&self.pool).await?;
+                    Ok(row)
+                }
+
+                async fn create_scheme(&self, playgroup_id: i32, name: &str, definition: &str) -> Result<SchemeRow, StoreError> {
+                    let row: SchemeRow = sqlx::query_as("INSERT INTO scoring_schemes (playgroup_id, name, definition) VALUES($1, $2, $3) RETURNING id, name, definition")
+                        .bind(playgroup_id)
+                        .bind(name)
+                        .bind(definition)
+                        .fetch_one(&self.pool).await?;
+                    Ok(row)
+                }
+
+                async fn update_scheme(&self, playgroup_id: i32, id: i32, name: &str, definition: &str) -> Result<Option<SchemeRow>, StoreError> {
+                    let row: Option<SchemeRow> = sqlx::query_as("UPDATE scoring_schemes SET name = $1, definition = $2 WHERE id = $3 AND playgroup_id = $4 RETURNING id, name, definition")
+                        .bind(name)
+                        .bind(definition)
+                        .bind(id)
+                        .bind(playgroup_id)
+                        .fetch_optional(&self.pool).await?;
+                    Ok(row)
+                }
+
+                async fn delete_scheme(&self, playgroup_id: i32, id: i32) -> Result<bool, StoreError> {
+                    let result = sqlx::query("DELETE FROM scoring_schemes WHERE id = $1 AND playgroup_id = $2").bind(id).bind(playgroup_id).execute(&self.pool).await?;
+                    Ok(result.rows_affected() > 0)
+                }
+            }
+        }
+    };
+}
+
+pub(super) use sql_store;
@@ -0,0 +1,86 @@
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use super::{StoreError, sql::sql_store};
+
+// Everything in a single file, for small deployments that don't want to run postgres
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    pub async fn connect(path: &str) -> Result<Self, StoreError> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .foreign_keys(true);
+
+        // SQLite only has one writer at a time anyway. A single connection
+        // means transactions never have to wait on each other's locks,
+        // which is what LOCK TABLE and FOR UPDATE do for postgres.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options).await?;
+
+        sqlx::migrate!("./migrations/sqlite").run(&pool).await?;
+
+        Ok(SqliteStore { pool })
+    }
+}
+
+// Same shape as the postgres GAME_SELECT. SQLite forgets that an object
+// is JSON when it's aggregated with an ORDER BY, so the players array is
+// joined together by hand. The commanders go through json() so they're
+// nested as an array rather than as a string.
+const GAME_SELECT: &str = "SELECT games.id, games.start_datetime, games.end_datetime,
+    (
+        SELECT '[' || COALESCE(group_concat(json_object(
+            'id', players.id,
+            'name', players.name,
+            'rank', games_players.rank,
+            'commanders', json((
+                SELECT json_group_array(commander ORDER BY commanders.id)
+                FROM commanders WHERE games_players_id = games_players.id
+            ))
+        ), ',' ORDER BY games_players.rank, players.name), '') || ']'
+        FROM games_players INNER JOIN players ON player_id = players.id
+        WHERE game_id = games.id
+    ) AS players,
+    (SELECT username FROM users WHERE users.id = games.submitted_by) AS submitted_by
+    FROM games
+    WHERE games.deleted_at IS NULL";
+
+// Same filters as postgres. A negative LIMIT is no limit at all.
+const GAME_FILTER: &str = "
+    AND ($1 IS NULL OR games.start_datetime >= $1)
+    AND ($2 IS NULL OR games.start_datetime < $2)
+    AND ($3 IS NULL OR EXISTS (
+        SELECT 1 FROM games_players INNER JOIN players ON player_id = players.id
+        WHERE game_id = games.id AND players.name = $3))
+    AND ($4 IS NULL OR EXISTS (
+        SELECT 1 FROM games_players INNER JOIN commanders ON games_players_id = games_players.id
+        WHERE game_id = games.id AND commander = $4))
+    AND ($5 IS NULL OR (SELECT COUNT(*) FROM games_players WHERE game_id = games.id) = $5)
+    AND ($6 IS NULL OR (games.start_datetime, games.id) < (SELECT start_datetime, id FROM games WHERE id = $6 AND playgroup_id = $8))
+    AND games.playgroup_id = $8
+    ORDER BY games.start_datetime DESC, games.id DESC
+    LIMIT COALESCE($7, -1)";
+
+const AUDIT_SELECT: &str = "SELECT audit_log.id, users.username, entity, entity_id, action, created_at, before, after
+    FROM audit_log LEFT JOIN users ON actor_id = users.id
+    WHERE playgroup_id = $1
+    AND ($2 IS NULL OR entity = $2)
+    AND ($3 IS NULL OR entity_id = $3)
+    AND ($4 IS NULL OR action = $4)
+    AND ($5 IS NULL OR users.username = $5)
+    AND ($6 IS NULL OR created_at >= $6)
+    AND ($7 IS NULL OR created_at < $7)
+    AND ($8 IS NULL OR audit_log.id < $8)
+    ORDER BY audit_log.id DESC
+    LIMIT $9";
+
+// Nothing to lock, see connect
+const LOCK_GAME: &str = "";
+
+const LOCK_RATINGS: Option<&str> = None;
+
+sql_store!(SqliteStore, sqlx::Sqlite, sqlx::types::Json<Vec<String>>);
@@ -0,0 +1,445 @@
+use axum::{body::{self, Body}, http::{Request, StatusCode}, response::IntoResponse, Router};
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+use crate::{accounts::hash_token, commanders::{CommanderCard, Commanders, Pairing}, error::ServerError, store::{MemoryStore, SharedStore, SqliteStore, StoreError}};
+
+const ADMIN_TOKEN: &str = "admin-token";
+const MEMBER_TOKEN: &str = "member-token";
+
+async fn setup(store: SharedStore) -> Router {
+    setup_with_commanders(store, Commanders::new([
+        ("Atraxa, Grand Unifier", None),
+        ("Atraxa, Praetors' Voice", None),
+        ("Edgar Markov", None),
+        ("Krenko, Mob Boss", None),
+        ("Raised by Giants", Some(Pairing::Background)),
+        ("Jecht, Reluctant Guardian", Some(Pairing::PartnerVariant { variant: String::from("Father & son") })),
+        ("Thrasios, Triton Hero", Some(Pairing::Partner)),
+        ("Tidus, Blitzball Star", Some(Pairing::PartnerVariant { variant: String::from("Father & son") })),
+        ("Tymna the Weaver", Some(Pairing::Partner)),
+        ("Wilson, Refined Grizzly", Some(Pairing::ChooseABackground)),
+    ].map(|(name, pairing)| CommanderCard { name: String::from(name), pairing }).to_vec())).await
+}
+
+// An admin and a user who isn't a member of any playgroup, both already
+// logged in so the tests don't pay for hashing passwords
+async fn setup_with_commanders(store: SharedStore, commanders: Commanders) -> Router {
+    let expires_at = Utc::now() + Duration::days(1);
+
+    for (username, token, admin) in [("admin", ADMIN_TOKEN, true), ("member", MEMBER_TOKEN, false)] {
+        store.create_user(username, "unused", admin).await.unwrap();
+        let user_id = store.user_id(username).await.unwrap().unwrap();
+        store.create_session(user_id, &hash_token(token), expires_at).await.unwrap();
+    }
+
+    crate::app(store, commanders)
+}
+
+fn memory_store() -> SharedStore {
+    Arc::new(MemoryStore::new())
+}
+
+async fn sqlite_store() -> SharedStore {
+    Arc::new(SqliteStore::connect(":memory:").await.unwrap())
+}
+
+// Runs each test against MemoryStore and an in-memory SQLite database,
+// so the SQL gets checked against the same expectations
+macro_rules! store_tests {
+    ($($test:ident),* $(,)?) => {
+        mod memory {
+            $(
+                #[tokio::test]
+                async fn $test() {
+                    super::$test(super::memory_store()).await
+                }
+            )*
+        }
+
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $test() {
+                    super::$test(super::sqlite_store().await).await
+                }
+            )*
+        }
+    };
+}
+
+store_tests!(
+    posted_games_are_listed,
+    rejects_bad_writes,
+    unknown_commanders_can_be_allowed,
+    games_are_recorded_before_the_commander_list_is,
+    malformed_input_gets_json_errors,
+    commanders_must_be_allowed_to_pair,
+    partner_variants_only_pair_with_the_same_variant,
+    games_can_be_edited_deleted_and_restored,
+    ratings_follow_results,
+    admin_writes_need_an_admin,
+    users_manage_their_own_tokens,
+    playgroups_are_isolated,
+);
+
+async fn send(app: &Router, method: &str, uri: &str, token: Option<&str>, body: Option<Value>) -> (StatusCode, Value) {
+    let mut request = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        request = request.header("Authorization", format!("Bearer {}", token));
+    }
+    let request = match body {
+        Some(body) => request.header("Content-Type", "application/json").body(Body::from(body.to_string())),
+        None => request.body(Body::empty())
+    }.unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+fn game(players: &[(&str, &str, usize)]) -> Value {
+    json!({
+        "start_datetime": "2024-01-01T18:00:00Z",
+        "end_datetime": "2024-01-01T19:30:00Z",
+        "players": players.iter().map(|(name, commander, rank)| json!({ "name": name, "commanders": [commander], "rank": rank })).collect::<Vec<_>>()
+    })
+}
+
+async fn add_players(app: &Router, names: &[&str]) {
+    for name in names {
+        let (status, _) = send(app, "POST", "/api/players", Some(ADMIN_TOKEN), Some(json!({ "name": name }))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+async fn posted_games_are_listed(store: SharedStore) {
+    let app = setup(store).await;
+    add_players(&app, &["alice", "bob"]).await;
+
+    let (status, _) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(game(&[("bob", "Krenko, Mob Boss", 2), ("alice", "Atraxa, Praetors' Voice", 1)]))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(&app, "GET", "/api/games", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let games = body["games"].as_array().unwrap();
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0]["submitted_by"], "admin");
+    assert_eq!(games[0]["players"][0]["name"], "alice");
+    assert_eq!(games[0]["players"][1]["commanders"], json!(["Krenko, Mob Boss"]));
+
+    let id = games[0]["id"].as_i64().unwrap();
+    let (status, body) = send(&app, "GET", &format!("/api/games/{}", id), None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["game"]["id"], id);
+}
+
+async fn rejects_bad_writes(store: SharedStore) {
+    let app = setup(store).await;
+    add_players(&app, &["alice"]).await;
+
+    let (status, body) = send(&app, "POST", "/api/players", Some(ADMIN_TOKEN), Some(json!({ "name": "alice" }))).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "PLAYER_EXISTS");
+
+    let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(game(&[("alice", "Krenko, Mob Boss", 1), ("carol", "Edgar Markov", 2)]))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "UNKNOWN_PLAYER");
+    assert_eq!(body["player"], "carol");
+
+    let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(game(&[("alice", "Krenko, Mob Boss", 1), ("alice", "Edgar Markov", 2)]))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "DUPLICATE_PLAYER");
+
+    let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(game(&[("alice", "Krenko, Mob Boss", 1), ("bob", "Edgar Markov", 3)]))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body, json!({
+        "success": false,
+        "error": "Player bob has invalid value for rank",
+        "code": "INVALID_RANK",
+        "player": "bob",
+        "rank": 3
+    }));
+
+    let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(game(&[("alice", "Atraxa", 1), ("bob", "Edgar Markov", 2)]))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "UNKNOWN_COMMANDER");
+    assert_eq!(body["suggestions"], json!(["Atraxa, Grand Unifier", "Atraxa, Praetors' Voice"]));
+
+    let mut bad_datetime = game(&[("alice", "Krenko, Mob Boss", 1), ("bob", "Edgar Markov", 2)]);
+    bad_datetime["end_datetime"] = json!("yesterday");
+    let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(bad_datetime)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "INVALID_JSON");
+
+    let (status, body) = send(&app, "POST", "/api/players", None, Some(json!({ "name": "bob" }))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "MISSING_TOKEN");
+
+    let (status, body) = send(&app, "POST", "/api/players", Some(MEMBER_TOKEN), Some(json!({ "name": "bob" }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "NOT_A_MEMBER");
+
+    let (_, body) = send(&app, "GET", "/api/players", None, None).await;
+    assert_eq!(body["names"], json!(["alice"]));
+}
+
+async fn unknown_commanders_can_be_allowed(store: SharedStore) {
+    let app = setup(store).await;
+    add_players(&app, &["alice", "bob"]).await;
+    let new_card = game(&[("alice", "Krenko, Tin Street Kingpin", 1), ("bob", "Edgar Markov", 2)]);
+
+    let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(new_card.clone())).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "Player \"alice\" has unknown commander \"Krenko, Tin Street Kingpin\"");
+
+    let (status, _) = send(&app, "POST", "/api/games?allow_unknown_commanders=true", Some(ADMIN_TOKEN), Some(new_card.clone())).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A game that isn't there is reported as such before its commanders are looked at
+    let (status, body) = send(&app, "PUT", "/api/games/999", Some(ADMIN_TOKEN), Some(new_card)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "GAME_NOT_FOUND");
+}
+
+async fn games_are_recorded_before_the_commander_list_is(store: SharedStore) {
+    let app = setup_with_commanders(store, Commanders::default()).await;
+    add_players(&app, &["alice", "bob"]).await;
+
+    let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(game(&[("alice", "Krenko, Tin Street Kingpin", 1), ("bob", "Edgar Markov", 2)]))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+async fn malformed_input_gets_json_errors(store: SharedStore) {
+    let app = setup(store).await;
+
+    let (status, body) = send(&app, "GET", "/api/games?limit=abc", None, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "INVALID_QUERY");
+
+    let (status, body) = send(&app, "GET", "/api/games/abc", None, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "INVALID_PATH");
+
+    let (status, body) = send(&app, "POST", "/api/players", Some(ADMIN_TOKEN), Some(json!({ "nmae": "alice" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "INVALID_JSON");
+
+    let request = Request::post("/api/players")
+        .header("Authorization", format!("Bearer {}", ADMIN_TOKEN))
+        .body(Body::from("{\"name\": \"alice\"}"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let bytes = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["code"], "MISSING_JSON_CONTENT_TYPE");
+
+    let (status, body) = send(&app, "POST", "/api/players", Some(ADMIN_TOKEN), Some(json!({ "name": "a".repeat(3 * 1024 * 1024) }))).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["code"], "PAYLOAD_TOO_LARGE");
+}
+
+async fn commanders_must_be_allowed_to_pair(store: SharedStore) {
+    let app = setup(store).await;
+    add_players(&app, &["alice", "bob"]).await;
+    let pairs = |first: &str, second: &str| {
+        let mut game = game(&[("alice", first, 1), ("bob", "Edgar Markov", 2)]);
+        game["players"][0]["commanders"] = json!([first, second]);
+        game
+    };
+
+    for (first, second) in [("Thrasios, Triton Hero", "Tymna the Weaver"), ("Raised by Giants", "Wilson, Refined Grizzly")] {
+        let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(pairs(first, second))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(pairs("Krenko, Mob Boss", "Edgar Markov"))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "ILLEGAL_PAIR");
+    assert_eq!(body["commanders"], json!(["Krenko, Mob Boss", "Edgar Markov"]));
+
+    let (_, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(pairs("Krenko, Mob Boss", "Tymna the Weaver"))).await;
+    assert_eq!(body["error"], "Player \"alice\" has commanders that can't be played together, \"Tymna the Weaver\" can only be paired with another commander with Partner");
+
+    let (_, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(pairs("Thrasios, Triton Hero", "Raised by Giants"))).await;
+    assert_eq!(body["code"], "ILLEGAL_PAIR");
+}
+
+async fn partner_variants_only_pair_with_the_same_variant(store: SharedStore) {
+    let app = setup(store).await;
+    add_players(&app, &["alice", "bob"]).await;
+    let pairs = |first: &str, second: &str| {
+        let mut game = game(&[("alice", first, 1), ("bob", "Edgar Markov", 2)]);
+        game["players"][0]["commanders"] = json!([first, second]);
+        game
+    };
+
+    let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(pairs("Jecht, Reluctant Guardian", "Tidus, Blitzball Star"))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(pairs("Tidus, Blitzball Star", "Thrasios, Triton Hero"))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "Player \"alice\" has commanders that can't be played together, \"Tidus, Blitzball Star\" can only be paired with another commander with Partner—Father & son");
+
+    let (status, _) = send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(pairs("Thrasios, Triton Hero", "Jecht, Reluctant Guardian"))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+async fn games_can_be_edited_deleted_and_restored(store: SharedStore) {
+    let app = setup(store).await;
+    add_players(&app, &["alice", "bob"]).await;
+    send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(game(&[("alice", "Krenko, Mob Boss", 1), ("bob", "Edgar Markov", 2)]))).await;
+    let (_, body) = send(&app, "GET", "/api/games", None, None).await;
+    let uri = format!("/api/games/{}", body["games"][0]["id"]);
+
+    let (status, body) = send(&app, "PATCH", &uri, Some(ADMIN_TOKEN), Some(game(&[("alice", "Krenko, Mob Boss", 2), ("bob", "Edgar Markov", 1)]))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (_, body) = send(&app, "GET", &uri, None, None).await;
+    assert_eq!(body["game"]["players"][0]["name"], "bob");
+
+    // A PATCH is merged with the stored game and checked as a whole
+    let (status, body) = send(&app, "PATCH", &uri, Some(ADMIN_TOKEN), Some(json!({ "end_datetime": "2000-01-01T00:00:00Z" }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "INVALID_DATE_RANGE");
+    let (status, _) = send(&app, "PATCH", &uri, Some(ADMIN_TOKEN), Some(json!({ "players": [
+        { "name": "alice", "commanders": ["Krenko, Mob Boss"], "rank": 1 },
+        { "name": "bob", "commanders": ["Edgar Markov"], "rank": 2 }
+    ] }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = send(&app, "GET", &uri, None, None).await;
+    assert_eq!(body["game"]["players"][0]["name"], "alice");
+
+    let (status, _) = send(&app, "DELETE", &uri, Some(ADMIN_TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, "GET", &uri, None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = send(&app, "POST", &format!("{}/restore", uri), Some(ADMIN_TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, "GET", &uri, None, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send(&app, "GET", "/api/audit", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let actions: Vec<&str> = body["entries"].as_array().unwrap().iter().map(|entry| entry["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["restore", "delete", "update", "update", "create", "create", "create"]);
+
+    let (status, _) = send(&app, "GET", "/api/audit?limit=18446744073709551615", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send(&app, "GET", "/api/audit?limit=18446744073709551616", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "INVALID_QUERY");
+}
+
+async fn ratings_follow_results(store: SharedStore) {
+    let app = setup(store).await;
+    add_players(&app, &["alice", "bob"]).await;
+    send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(game(&[("alice", "Krenko, Mob Boss", 1), ("bob", "Edgar Markov", 2)]))).await;
+
+    let (status, body) = send(&app, "GET", "/api/ratings", None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["ratings"][0]["name"], "alice");
+    assert!(body["ratings"][0]["rating"].as_f64().unwrap() > body["ratings"][1]["rating"].as_f64().unwrap());
+
+    // A game entered late only replays what came after it, picking up from the
+    // ratings before it, which has to end up where replaying everything does
+    for (day, winner, loser) in [(3, "bob", "alice"), (2, "alice", "bob")] {
+        let mut later = game(&[(winner, "Krenko, Mob Boss", 1), (loser, "Edgar Markov", 2)]);
+        later["start_datetime"] = json!(format!("2024-01-0{}T18:00:00Z", day));
+        later["end_datetime"] = json!(format!("2024-01-0{}T19:00:00Z", day));
+        send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(later)).await;
+    }
+    let (_, incremental) = send(&app, "GET", "/api/ratings", None, None).await;
+
+    let (status, _) = send(&app, "POST", "/api/ratings/rebuild", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, rebuilt) = send(&app, "GET", "/api/ratings", None, None).await;
+    assert_eq!(incremental, rebuilt);
+    assert_eq!(rebuilt["ratings"][0]["games"], 3);
+}
+
+async fn admin_writes_need_an_admin(store: SharedStore) {
+    let app = setup(store).await;
+    let (status, _) = send(&app, "POST", "/api/members", Some(ADMIN_TOKEN), Some(json!({ "username": "member" }))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Members can write games but a password login doesn't make them an admin
+    let season = json!({ "name": "Winter", "start_datetime": "2024-01-01T00:00:00Z", "end_datetime": "2024-04-01T00:00:00Z" });
+    let (status, body) = send(&app, "POST", "/api/seasons", Some(MEMBER_TOKEN), Some(season.clone())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "MISSING_SCOPE");
+
+    let (status, _) = send(&app, "POST", "/api/seasons", Some(ADMIN_TOKEN), Some(season)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+async fn users_manage_their_own_tokens(store: SharedStore) {
+    let app = setup(store).await;
+
+    let (status, body) = send(&app, "POST", "/api/tokens", Some(MEMBER_TOKEN), Some(json!({ "name": "scores", "scopes": ["games:write"] }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let id = body["token"]["id"].as_i64().unwrap();
+    let secret = body["secret"].as_str().unwrap().to_string();
+
+    let (status, body) = send(&app, "POST", "/api/tokens", Some(MEMBER_TOKEN), Some(json!({ "name": "everything", "scopes": ["admin"] }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "SCOPE_NOT_ALLOWED");
+
+    let (_, body) = send(&app, "GET", "/api/tokens", Some(MEMBER_TOKEN), None).await;
+    assert_eq!(body["tokens"].as_array().unwrap().len(), 1);
+    let (_, body) = send(&app, "GET", "/api/tokens", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(body["tokens"], json!([]));
+    let (_, body) = send(&app, "GET", "/api/users/member/tokens", Some(ADMIN_TOKEN), None).await;
+    assert_eq!(body["tokens"][0]["id"], id);
+
+    // A token can't make a token that outlives it
+    let (status, body) = send(&app, "POST", "/api/tokens", Some(&secret), Some(json!({ "name": "forever", "scopes": ["games:write"] }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "LOGIN_REQUIRED");
+    let (status, _) = send(&app, "DELETE", &format!("/api/tokens/{}", id), Some(&secret), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = send(&app, "DELETE", &format!("/api/tokens/{}", id), Some(MEMBER_TOKEN), None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+async fn playgroups_are_isolated(store: SharedStore) {
+    let app = setup(store).await;
+    let (status, _) = send(&app, "POST", "/api/groups", Some(ADMIN_TOKEN), Some(json!({ "slug": "friday", "name": "Friday" }))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send(&app, "POST", "/api/groups/friday/players", Some(ADMIN_TOKEN), Some(json!({ "name": "alice" }))).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = send(&app, "GET", "/api/groups/friday/players", None, None).await;
+    assert_eq!(body["names"], json!(["alice"]));
+    let (_, body) = send(&app, "GET", "/api/players", None, None).await;
+    assert_eq!(body["names"], json!([]));
+
+    // Game IDs are shared between playgroups, a cursor from another one isn't a page boundary here
+    add_players(&app, &["alice", "bob"]).await;
+    send(&app, "POST", "/api/games", Some(ADMIN_TOKEN), Some(game(&[("alice", "Krenko, Mob Boss", 1), ("bob", "Edgar Markov", 2)]))).await;
+    let (_, body) = send(&app, "GET", "/api/games", None, None).await;
+    let id = body["games"][0]["id"].as_i64().unwrap();
+    let (status, body) = send(&app, "GET", &format!("/api/groups/friday/games?before={}", id), None, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["code"], "UNKNOWN_CURSOR");
+
+    let (status, _) = send(&app, "GET", "/api/groups/saturday/players", None, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn database_outages_are_reported() {
+    let response = ServerError::from(StoreError::Database(sqlx::Error::PoolTimedOut)).into_response();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let bytes = body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body: Value = serde_json::from_slice(&bytes).unwrap();
+    assert_eq!(body["code"], "DATABASE_UNAVAILABLE");
+
+    let response = ServerError::from(StoreError::Inconsistent(String::from("Unknown audit action"))).into_response();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
@@ -0,0 +1,219 @@
+use itertools::Itertools;
+use std::{collections::HashSet, fmt};
+use crate::messages::{ApiError, CreateGamePayload};
+
+// Everything that can be wrong with a game without looking it up anywhere.
+// The server refuses games with any of these and the client shows them
+// next to the form before anything is sent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameError {
+    TooFewPlayers,
+    DuplicatePlayer { name: String },
+    EndNotAfterStart,
+    // Ranks go from 1 to the number of players
+    RankOutOfRange { name: String, rank: usize },
+    NoFirstPlace,
+    // A rank has to be its position in the sorted ranks or a tie with the one before
+    InvalidRankSequence { rank: usize, expected: usize, previous: usize },
+    NoCommanders { name: String },
+    EmptyCommander { name: String },
+    // Even with partners there's only ever two
+    TooManyCommanders { name: String },
+}
+
+impl From<GameError> for ApiError {
+    fn from(error: GameError) -> Self {
+        match error {
+            GameError::TooFewPlayers => ApiError::TooFewPlayers,
+            GameError::DuplicatePlayer { name } => ApiError::DuplicatePlayer { player: name },
+            GameError::EndNotAfterStart => ApiError::InvalidDateRange,
+            GameError::RankOutOfRange { name, rank } => ApiError::InvalidRank { player: name, rank },
+            GameError::NoFirstPlace => ApiError::NoFirstPlace,
+            GameError::InvalidRankSequence { rank, expected, previous } => ApiError::InvalidRankSequence { rank, expected, previous },
+            GameError::NoCommanders { name } => ApiError::NoCommanders { player: name },
+            GameError::EmptyCommander { name } => ApiError::EmptyCommander { player: name },
+            GameError::TooManyCommanders { name } => ApiError::TooManyCommanders { player: name },
+        }
+    }
+}
+
+// The same message the server sends for it
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", ApiError::from(self.clone()))
+    }
+}
+
+impl std::error::Error for GameError {}
+
+impl GameError {
+    // The player the problem is about, if it's about one in particular
+    pub fn player(&self) -> Option<&str> {
+        match self {
+            GameError::DuplicatePlayer { name }
+                | GameError::RankOutOfRange { name, .. }
+                | GameError::NoCommanders { name }
+                | GameError::EmptyCommander { name }
+                | GameError::TooManyCommanders { name } => Some(name),
+            _ => None
+        }
+    }
+}
+
+// The first problem in the order they are checked
+pub fn validate_game(payload: &CreateGamePayload) -> Result<(), GameError> {
+    match game_errors(payload).into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(())
+    }
+}
+
+// Every problem with the game at once, so a form can show them all
+pub fn game_errors(payload: &CreateGamePayload) -> Vec<GameError> {
+    let mut errors = Vec::new();
+
+    if payload.players.len() < 2 {
+        errors.push(GameError::TooFewPlayers);
+    }
+
+    let mut seen = HashSet::new();
+    for player in payload.players.iter() {
+        if !seen.insert(&player.name) {
+            errors.push(GameError::DuplicatePlayer { name: player.name.clone() });
+        }
+    }
+
+    if payload.end_datetime <= payload.start_datetime {
+        errors.push(GameError::EndNotAfterStart);
+    }
+
+    errors.extend(rank_errors(payload));
+
+    for player in payload.players.iter() {
+        if player.commanders.is_empty() {
+            errors.push(GameError::NoCommanders { name: player.name.clone() });
+        }
+        if player.commanders.iter().any(|commander| commander.is_empty()) {
+            errors.push(GameError::EmptyCommander { name: player.name.clone() });
+        }
+        if player.commanders.len() > 2 {
+            errors.push(GameError::TooManyCommanders { name: player.name.clone() });
+        }
+    }
+
+    errors
+}
+
+fn rank_errors(payload: &CreateGamePayload) -> Vec<GameError> {
+    // If any ranks are outside the bounds of 1-<number of players>
+    // then the ranking is invalid and there's no point checking the order
+    let out_of_range: Vec<GameError> = payload.players
+        .iter()
+        .filter(|player| player.rank < 1 || player.rank > payload.players.len())
+        .map(|player| GameError::RankOutOfRange { name: player.name.clone(), rank: player.rank })
+        .collect();
+
+    if !out_of_range.is_empty() || payload.players.len() < 2 {
+        return out_of_range;
+    }
+
+    // The way we validate ranks is by ensuring that the first in sorted_ranks
+    // rank is 1. After that we ensure the second rank is either equal to the
+    // previous or equal to 2, then we check that third rank in order is equal to
+    // 3 or the previous rank and so on.
+    let sorted_ranks: Vec<usize> = payload.players
+        .iter()
+        .map(|player| player.rank)
+        .sorted()
+        .collect();
+
+    if sorted_ranks[0] != 1 {
+        return vec![GameError::NoFirstPlace];
+    }
+
+    // The only gotcha is that we have to start index at 2
+    // because rankings start at 1 and due to the way tuple_windows
+    // makes pairs the first cur is actually the second element in the list
+    sorted_ranks
+        .into_iter()
+        .tuple_windows()
+        .enumerate()
+        .map(|(index, (prev, cur))| (index + 2, prev, cur))
+        .filter(|(index, prev, cur)| index != cur && prev != cur)
+        .map(|(index, prev, cur)| GameError::InvalidRankSequence { rank: cur, expected: index, previous: prev })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use crate::messages::Player;
+    use super::*;
+
+    // An hour long game, each player is (name, commanders, rank)
+    fn game(players: &[(&str, &[&str], usize)]) -> CreateGamePayload {
+        let start_datetime = Utc.with_ymd_and_hms(2024, 1, 1, 18, 0, 0).unwrap();
+
+        CreateGamePayload {
+            start_datetime,
+            end_datetime: start_datetime + Duration::hours(1),
+            players: players.iter().map(|(name, commanders, rank)| Player {
+                id: None,
+                name: name.to_string(),
+                commanders: commanders.iter().map(|commander| commander.to_string()).collect(),
+                rank: *rank
+            }).collect(),
+        }
+    }
+
+    #[test]
+    fn valid_games_pass() {
+        assert_eq!(validate_game(&game(&[("alice", &["Krenko"], 1), ("bob", &["Thrasios", "Tymna"], 2)])), Ok(()));
+        assert_eq!(validate_game(&game(&[("alice", &["Krenko"], 1), ("bob", &["Edgar"], 1), ("carol", &["Atraxa"], 3)])), Ok(()));
+    }
+
+    #[test]
+    fn players_can_only_be_in_a_game_once() {
+        let errors = game_errors(&game(&[("alice", &["Krenko"], 1), ("bob", &["Edgar"], 2), ("alice", &["Atraxa"], 3)]));
+        assert_eq!(errors, [GameError::DuplicatePlayer { name: String::from("alice") }]);
+
+        assert_eq!(game_errors(&game(&[("alice", &["Krenko"], 1)])), [GameError::TooFewPlayers]);
+    }
+
+    #[test]
+    fn ranks_must_be_in_range_and_in_sequence() {
+        let errors = game_errors(&game(&[("alice", &["Krenko"], 0), ("bob", &["Edgar"], 3)]));
+        assert_eq!(errors, [
+            GameError::RankOutOfRange { name: String::from("alice"), rank: 0 },
+            GameError::RankOutOfRange { name: String::from("bob"), rank: 3 },
+        ]);
+
+        let errors = game_errors(&game(&[("alice", &["Krenko"], 2), ("bob", &["Edgar"], 2)]));
+        assert_eq!(errors, [GameError::NoFirstPlace]);
+
+        // A tie for first means the next player is third, not second
+        let errors = game_errors(&game(&[("alice", &["Krenko"], 1), ("bob", &["Edgar"], 1), ("carol", &["Atraxa"], 2)]));
+        assert_eq!(errors, [GameError::InvalidRankSequence { rank: 2, expected: 3, previous: 1 }]);
+    }
+
+    #[test]
+    fn games_must_end_after_they_start() {
+        let mut payload = game(&[("alice", &["Krenko"], 1), ("bob", &["Edgar"], 2)]);
+        payload.end_datetime = payload.start_datetime;
+        assert_eq!(validate_game(&payload), Err(GameError::EndNotAfterStart));
+
+        payload.end_datetime = payload.start_datetime - Duration::minutes(1);
+        assert_eq!(validate_game(&payload), Err(GameError::EndNotAfterStart));
+    }
+
+    #[test]
+    fn every_player_needs_one_or_two_named_commanders() {
+        let errors = game_errors(&game(&[("alice", &[], 1), ("bob", &[""], 2), ("carol", &["Thrasios", "Tymna", "Kraum"], 3)]));
+        assert_eq!(errors, [
+            GameError::NoCommanders { name: String::from("alice") },
+            GameError::EmptyCommander { name: String::from("bob") },
+            GameError::TooManyCommanders { name: String::from("carol") },
+        ]);
+        assert_eq!(errors[1].player(), Some("bob"));
+    }
+}